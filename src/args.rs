@@ -4,10 +4,17 @@
 //! It will ensure we get the correct args and then return
 //! a correct Structure to run the corresponding commands
 use crate::{
-    commands::{clear, drop, list, record, run, RunnableCommand},
-    errors::ReplayResult,
+    commands::{
+        clear, config as config_cmd, drop, edit, export, import, list, param, record, run, search,
+        show, tag, RunnableCommand,
+    },
+    config,
+    errors::{ReplayError, ReplayResult},
+    session::{params, SessionRef},
 };
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
 use clap::{Parser, Subcommand};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -27,10 +34,34 @@ pub enum CliCommand {
     /// List all the sessions recorded
     List(list::ListCommand),
 
+    /// Show the details and the commands of a session, last session if not specified
+    Show(show::ShowCommand),
+
+    /// Search a regular expression in the commands of all the sessions
+    Search(search::SearchCommand),
+
     /// Drop a specified session, last session if not specified
     Drop(drop::DropCommand),
-    /// Drop all the sessions recorded
+    /// Drop all the sessions recorded and their names, the config is kept
     Clear(clear::ClearCommand),
+
+    /// Edit the commands of a session in $EDITOR, last session if not specified
+    Edit(edit::EditCommand),
+
+    /// Export a specified session, last session if not specified
+    Export(export::ExportCommand),
+
+    /// Create a new session from a script, a shell history or stdin
+    Import(import::ImportCommand),
+
+    /// Give a name to a session, to refer to it regardless of its index
+    Tag(tag::TagCommand),
+
+    /// Turn a value in the commands of a session into a parameter set with `run --set`
+    Param(param::ParamCommand),
+
+    /// Read or change the default options stored in the configuration file
+    Config(config_cmd::ConfigCommand),
 }
 
 impl CliCommand {
@@ -39,8 +70,16 @@ impl CliCommand {
             CliCommand::Run(cmd) => cmd.run(),
             CliCommand::Record(cmd) => cmd.run(),
             CliCommand::List(cmd) => cmd.run(),
+            CliCommand::Show(cmd) => cmd.run(),
+            CliCommand::Search(cmd) => cmd.run(),
             CliCommand::Drop(cmd) => cmd.run(),
             CliCommand::Clear(cmd) => cmd.run(),
+            CliCommand::Edit(cmd) => cmd.run(),
+            CliCommand::Export(cmd) => cmd.run(),
+            CliCommand::Import(cmd) => cmd.run(),
+            CliCommand::Tag(cmd) => cmd.run(),
+            CliCommand::Param(cmd) => cmd.run(),
+            CliCommand::Config(cmd) => cmd.run(),
         }
     }
 }
@@ -50,17 +89,193 @@ pub fn parse_command(args: &[String]) -> ReplayResult<CliCommand> {
     Ok(cli_command.command)
 }
 
-pub fn parse_session_index(s: &str) -> Result<u32, String> {
-    s.strip_prefix("replay@{")
-        .and_then(|rest| rest.strip_suffix('}'))
-        .ok_or_else(|| {
+/// Parse a session given as `replay@{index}`, a session name or a session id prefix
+pub fn parse_session_index(s: &str) -> Result<SessionRef, String> {
+    if s.starts_with("replay@") {
+        return s
+            .strip_prefix("replay@{")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .ok_or_else(|| {
+                format!(
+                    "Session index must be of the form replay@{{index}}, got '{}'",
+                    s
+                )
+            })?
+            .parse::<u32>()
+            .map(SessionRef::Index)
+            .map_err(|_| format!("Invalid session index in '{}'", s));
+    }
+    validate_session_name(s)
+        .map(SessionRef::Name)
+        .map_err(|_| {
             format!(
-                "Session name must be of the form replay@{{index}}, got '{}'",
+                "Session must be of the form replay@{{index}}, a session name or an id prefix, got '{}'",
                 s
             )
-        })?
-        .parse::<u32>()
-        .map_err(|_| format!("Invalid session index in '{}'", s))
+        })
+}
+
+pub fn validate_session_name(s: &str) -> Result<String, String> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/');
+    if s.is_empty()
+        || s.starts_with("replay@")
+        || !s.starts_with(|c: char| c.is_ascii_alphanumeric())
+    {
+        return Err(format!("Invalid session name '{}'", s));
+    }
+    if !s.chars().all(valid_char) {
+        return Err(format!(
+            "Session name '{}' can only contain letters, digits, '-', '_', '.' and '/'",
+            s
+        ));
+    }
+    Ok(String::from(s))
+}
+
+/// Parse a session parameter given as `name=value`
+pub fn parse_parameter(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Parameter must be of the form name=value, got '{}'", s))?;
+    if !params::is_valid_name(name) {
+        return Err(format!(
+            "Invalid parameter name '{}', only letters, digits and '_' are allowed",
+            name
+        ));
+    }
+    Ok((String::from(name), String::from(value)))
+}
+
+pub fn validate_env_name(s: &str) -> Result<String, String> {
+    // Same rules as the parameter names, which are valid shell variable names
+    if !params::is_valid_name(s) {
+        return Err(format!(
+            "Invalid environment variable name '{}', only letters, digits and '_' are allowed",
+            s
+        ));
+    }
+    Ok(String::from(s))
+}
+
+/// Parse an environment variable given as `NAME=value`
+pub fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once('=').ok_or_else(|| {
+        format!(
+            "Environment variable must be of the form NAME=value, got '{}'",
+            s
+        )
+    })?;
+    Ok((validate_env_name(name)?, String::from(value)))
+}
+
+/// Replay speed factor, kept in thousandths so it can be compared exactly
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Speed(u32);
+
+impl Speed {
+    pub fn apply(self, duration: Duration) -> Duration {
+        duration * 1000 / self.0
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed(1000)
+    }
+}
+
+/// Parse a speed factor such as `2`, `2x` or `0.5x`
+pub fn parse_speed(s: &str) -> Result<Speed, String> {
+    let factor: f64 = s
+        .strip_suffix('x')
+        .unwrap_or(s)
+        .parse()
+        .map_err(|_| format!("Invalid speed '{}', expected a factor like 2x or 0.5x", s))?;
+    let thousandths = (factor * 1000.0).round();
+    if !(1.0..=1_000_000.0).contains(&thousandths) {
+        return Err(format!(
+            "Speed must be between 0.001x and 1000x, got '{}'",
+            s
+        ));
+    }
+    Ok(Speed(thousandths as u32))
+}
+
+/// Parse a duration such as `500ms`, `2s` or `1m`, in seconds without unit
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration '{}', expected a value like 500ms, 2s or 1m",
+            s
+        )
+    };
+    let (value, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value: f64 = value.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "s" => value,
+        "m" => value * 60.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Parse a date given as `YYYY-MM-DD`, RFC 3339 or a duration before now (`30m`, `12h`, `7d`)
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN).and_utc());
+    }
+    let invalid = || {
+        format!(
+            "Invalid date '{}', expected YYYY-MM-DD, RFC 3339 or a duration like 7d",
+            s
+        )
+    };
+    let (amount, unit) = s.split_at(s.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let duration = match unit {
+        "s" => TimeDelta::try_seconds(amount),
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)?;
+    Ok(Utc::now() - duration)
+}
+
+/// Parse a description given on the command line, its length is checked by
+/// `validate_session_description` once the config can be reported as such
+pub fn parse_session_description(s: &str) -> Result<String, String> {
+    if s.parse::<i32>().is_ok() {
+        return Err(String::from("Session description cannot be an integer"));
+    }
+    Ok(String::from(s))
+}
+
+/// Check a description against the bounds of the config
+pub fn validate_session_description(s: &str) -> ReplayResult<String> {
+    let config = &config::get()?.record;
+    if s.len() < config.description_min_len {
+        return Err(ReplayError::SessionError(format!(
+            "Session description is too short (min {} chars)",
+            config.description_min_len
+        )));
+    }
+    if s.len() > config.description_max_len {
+        return Err(ReplayError::SessionError(format!(
+            "Session description is too long (max {} chars)",
+            config.description_max_len
+        )));
+    }
+
+    parse_session_description(s).map_err(ReplayError::SessionError)
 }
 
 // TODO move it as integration tests
@@ -68,6 +283,7 @@ pub fn parse_session_index(s: &str) -> Result<u32, String> {
 mod tests {
     use super::*;
     use crate::errors::ReplayError;
+    use crate::export::ExportFormat;
 
     #[test]
     fn test_valid_record_command() {
@@ -80,6 +296,7 @@ mod tests {
         let expected_command = CliCommand::Record(record::RecordCommand::new(
             Some(String::from("\"test_valid_record_command\"")),
             false,
+            false,
         ));
         assert_eq!(expected_command, parse_command(&args).unwrap())
     }
@@ -94,17 +311,31 @@ mod tests {
             String::from("--delay"),
             String::from("10"),
         ];
-        let expected_command = CliCommand::Run(run::RunCommand::new(0, true, 10));
+        let expected_command =
+            CliCommand::Run(run::RunCommand::new(SessionRef::Index(0), true, Some(10)));
+        assert_eq!(expected_command, parse_command(&args).unwrap());
+
+        // Session given by name
+        let args = [
+            String::from("replay"),
+            String::from("run"),
+            String::from("deploy-staging"),
+        ];
+        let expected_command = CliCommand::Run(run::RunCommand::new(
+            SessionRef::Name(String::from("deploy-staging")),
+            false,
+            None,
+        ));
         assert_eq!(expected_command, parse_command(&args).unwrap());
     }
 
     #[test]
     fn test_invalid_run_command() {
-        // Invalid session name
+        // Invalid session index
         let args = [
             String::from("replay"),
             String::from("run"),
-            String::from("invalid_session_name"),
+            String::from("replay@{a}"),
             String::from("--show"),
             String::from("--delay"),
             String::from("10"),
@@ -112,6 +343,15 @@ mod tests {
         let res = parse_command(&args);
         assert!(matches!(res, Err(ReplayError::ClapError(_))));
 
+        // Invalid session name
+        let args = [
+            String::from("replay"),
+            String::from("run"),
+            String::from("invalid session name"),
+        ];
+        let res = parse_command(&args);
+        assert!(matches!(res, Err(ReplayError::ClapError(_))));
+
         // Delay as a char
         let args = [
             String::from("replay"),
@@ -139,36 +379,177 @@ mod tests {
 
     #[test]
     fn test_invalid_record_command() {
-        // Too short session description
+        // The description length is checked against the config when the command runs
         let args = [
             String::from("replay"),
             String::from("record"),
             String::from("to short"),
         ];
+        assert!(parse_command(&args).is_ok());
+        assert!(matches!(
+            validate_session_description("to short"),
+            Err(ReplayError::SessionError(_))
+        ));
+        assert!(matches!(
+            validate_session_description(
+                "this session description is way too long and exceeds the maximum length of eighty characters"
+            ),
+            Err(ReplayError::SessionError(_))
+        ));
+
+        // Invalid session description
+        let args = [
+            String::from("replay"),
+            String::from("record"),
+            String::from("1234567890"),
+        ];
         let res = parse_command(&args);
         assert!(matches!(res, Err(ReplayError::ClapError(_))));
+    }
 
-        // Too long session description
+    #[test]
+    fn test_valid_export_command() {
         let args = [
             String::from("replay"),
-            String::from("record"),
-            String::from(
-                "this session description is way too long and exceeds the maximum length of eighty characters",
-            ),
+            String::from("export"),
+            String::from("replay@{2}"),
+            String::from("--format"),
+            String::from("asciicast"),
+            String::from("-o"),
+            String::from("demo.cast"),
         ];
+        let expected_command = CliCommand::Export(export::ExportCommand::new(
+            SessionRef::Index(2),
+            ExportFormat::Asciicast,
+            Some("demo.cast".into()),
+        ));
+        assert_eq!(expected_command, parse_command(&args).unwrap());
+
+        // The format is mandatory
+        let args = [String::from("replay"), String::from("export")];
         let res = parse_command(&args);
         assert!(matches!(res, Err(ReplayError::ClapError(_))));
+    }
 
-        // Invalid session description
+    #[test]
+    fn test_tag_command() {
         let args = [
             String::from("replay"),
-            String::from("record"),
-            String::from("1234567890"),
+            String::from("tag"),
+            String::from("replay@{2}"),
+            String::from("deploy"),
+        ];
+        let expected_command = CliCommand::Tag(tag::TagCommand::new(
+            Some(SessionRef::Index(2)),
+            Some(String::from("deploy")),
+            None,
+        ));
+        assert_eq!(expected_command, parse_command(&args).unwrap());
+
+        let args = [
+            String::from("replay"),
+            String::from("tag"),
+            String::from("--delete"),
+            String::from("deploy"),
+        ];
+        let expected_command = CliCommand::Tag(tag::TagCommand::new(
+            None,
+            None,
+            Some(String::from("deploy")),
+        ));
+        assert_eq!(expected_command, parse_command(&args).unwrap());
+
+        // The name is missing
+        let args = [
+            String::from("replay"),
+            String::from("tag"),
+            String::from("replay@{2}"),
         ];
         let res = parse_command(&args);
         assert!(matches!(res, Err(ReplayError::ClapError(_))));
     }
 
+    #[test]
+    fn test_parse_parameter() {
+        assert_eq!(
+            parse_parameter("env=staging").unwrap(),
+            (String::from("env"), String::from("staging"))
+        );
+        assert_eq!(
+            parse_parameter("query=a=b").unwrap(),
+            (String::from("query"), String::from("a=b"))
+        );
+        assert_eq!(
+            parse_parameter("empty=").unwrap(),
+            (String::from("empty"), String::new())
+        );
+        assert!(parse_parameter("env").is_err());
+        assert!(parse_parameter("my-env=staging").is_err());
+        assert!(parse_parameter("=staging").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_var() {
+        assert_eq!(
+            parse_env_var("KUBECONFIG=/tmp/a=b").unwrap(),
+            (String::from("KUBECONFIG"), String::from("/tmp/a=b"))
+        );
+        assert!(parse_env_var("KUBECONFIG").is_err());
+        assert!(parse_env_var("1PATH=/bin").is_err());
+        assert!(validate_env_name("AWS_PROFILE").is_ok());
+        assert!(validate_env_name("AWS-PROFILE").is_err());
+    }
+
+    #[test]
+    fn test_parse_speed_and_duration() {
+        let second = Duration::from_secs(1);
+        assert_eq!(parse_speed("2x").unwrap().apply(second), second / 2);
+        assert_eq!(parse_speed("0.5").unwrap().apply(second), second * 2);
+        assert_eq!(Speed::default().apply(second), second);
+        assert!(parse_speed("0x").is_err());
+        assert!(parse_speed("-2x").is_err());
+        assert!(parse_speed("fast").is_err());
+
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2s").unwrap(), second * 2);
+        assert_eq!(parse_duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("1m").unwrap(), second * 60);
+        assert!(parse_duration("2h").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            parse_since("2024-05-01").unwrap().to_rfc3339(),
+            "2024-05-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_since("2024-05-01T12:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-05-01T10:00:00+00:00"
+        );
+        let since = parse_since("7d").unwrap();
+        let since = Utc::now() - since;
+        assert!(since >= TimeDelta::days(7) && since < TimeDelta::days(7) + TimeDelta::minutes(1));
+        assert!(parse_since("7w").is_err());
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_session_name_validation() {
+        assert!(validate_session_name("deploy").is_ok());
+        assert!(validate_session_name("deploy-staging_v1.2").is_ok());
+        assert!(validate_session_name("team/deploy").is_ok());
+        assert!(validate_session_name("").is_err());
+        assert!(validate_session_name("-deploy").is_err());
+        assert!(validate_session_name("replay@deploy").is_err());
+        assert!(validate_session_name("deploy staging").is_err());
+    }
+
     #[test]
     fn test_invalid_command() {
         let args = [
@@ -1,60 +1,298 @@
+/// Model of the line being edited in the shell, so the recorded command is the one
+/// readline executes. Keys are fed one byte at a time, as they are read in raw mode,
+/// and interpreted with the default readline (emacs) bindings.
+/// The keys using the history or the completion can't be followed, the line is then
+/// flagged as inexact.
 pub struct CharBuffer {
     buf: Vec<u8>,
+    cursor: usize,        // byte position of the cursor in `buf`
+    escape: Vec<u8>,      // escape sequence being read
+    kill_buffer: Vec<u8>, // last killed text, inserted back by Ctrl+Y
+    last_kill: bool,      // whether the previous key killed text
+    appending_kill: bool, // whether the current key follows a kill
+    pasting: bool,        // between the bracketed paste markers, keys are inserted as they are
+    exact: bool,          // no key changed the line in a way that can't be followed
 }
 
 impl CharBuffer {
     pub fn new() -> Self {
-        CharBuffer { buf: vec![] }
+        Self::from_vec(vec![])
     }
 
     pub fn from_vec(buf: Vec<u8>) -> Self {
-        CharBuffer { buf }
+        CharBuffer {
+            cursor: buf.len(),
+            buf,
+            escape: vec![],
+            kill_buffer: vec![],
+            last_kill: false,
+            appending_kill: false,
+            pasting: false,
+            exact: true,
+        }
+    }
+
+    /// Interpret a key read from the terminal
+    pub fn feed(&mut self, c: u8) {
+        if !self.escape.is_empty() {
+            self.escape.push(c);
+            self.handle_escape();
+            return;
+        }
+        if c != b'\x1b' {
+            self.start_key();
+        }
+        if self.pasting {
+            // Pasted lines are inserted, not run, until the end of the paste
+            match c {
+                b'\x1b' => self.escape.push(c),
+                b'\r' => self.push_char(b'\n'),
+                _ => self.push_char(c),
+            }
+            return;
+        }
+        match c {
+            b'\x1b' => self.escape.push(c),
+            // Ctrl+A
+            b'\x01' => self.cursor = 0,
+            // Ctrl+E
+            b'\x05' => self.cursor = self.buf.len(),
+            // Ctrl+B
+            b'\x02' => self.cursor = self.prev_char_start(),
+            // Ctrl+F
+            b'\x06' => self.cursor = self.next_char_start(),
+            // Backspace, Ctrl+H
+            b'\x7F' | b'\x08' => {
+                self.pop_char();
+            }
+            // Ctrl+D, readline only deletes when the line is not empty
+            b'\x04' => self.delete_char(),
+            // Ctrl+W
+            b'\x17' => {
+                self.pop_word();
+            }
+            // Ctrl+U
+            b'\x15' => self.kill(0, self.cursor),
+            // Ctrl+K
+            b'\x0B' => self.kill(self.cursor, self.buf.len()),
+            // Ctrl+Y
+            b'\x19' => {
+                let yanked = self.kill_buffer.clone();
+                self.insert(&yanked);
+            }
+            // Tab completes the line, it is kept as typed to complete it again when replayed
+            b'\t' => {
+                self.push_char(c);
+                self.exact = false;
+            }
+            // Ctrl+P, Ctrl+N, Ctrl+R, Ctrl+S browse the history
+            b'\x10' | b'\x0E' | b'\x12' | b'\x13' => self.exact = false,
+            // Ctrl+L redraws the line, Ctrl+G aborts nothing at the prompt
+            b'\x0C' | b'\x07' => {}
+            // Other control keys (Ctrl+T, Ctrl+_, Ctrl+V, Ctrl+X...) may change the line
+            // in ways that are not followed
+            0..=0x1F => self.exact = false,
+            _ => self.push_char(c),
+        }
     }
 
-    /// Return the popped character
-    pub fn pop_char(&mut self) -> Option<u8> {
-        self.buf.pop()
+    /// Handle `self.escape` once it is a complete sequence
+    fn handle_escape(&mut self) {
+        let seq = &self.escape[1..];
+        let action = match seq {
+            // Alt+B, Alt+F, Alt+D, Alt+Backspace
+            [b'b'] => EscapeAction::WordLeft,
+            [b'f'] => EscapeAction::WordRight,
+            [b'd'] => EscapeAction::KillWordRight,
+            [b'\x7F'] => EscapeAction::KillWordLeft,
+            // Alt+., Alt+_, Alt+< and Alt+> take the text from the history
+            [b'.' | b'_' | b'<' | b'>'] => EscapeAction::History,
+            // CSI (`ESC [`) and SS3 (`ESC O`) sequences end with a byte in 0x40..=0x7E
+            [b'[' | b'O', .., last] if (0x40..=0x7E).contains(last) => Self::csi_action(&seq[1..]),
+            [b'[' | b'O', ..] => return, // incomplete
+            // Alt+T, Alt+U, Alt+L, Alt+C, Alt+Y... are not followed
+            _ => EscapeAction::Unknown,
+        };
+        self.escape.clear();
+        self.start_key();
+        match action {
+            EscapeAction::Left => self.cursor = self.prev_char_start(),
+            EscapeAction::Right => self.cursor = self.next_char_start(),
+            EscapeAction::Home => self.cursor = 0,
+            EscapeAction::End => self.cursor = self.buf.len(),
+            EscapeAction::Delete => self.delete_char(),
+            EscapeAction::WordLeft => self.cursor = self.prev_word_start(),
+            EscapeAction::WordRight => self.cursor = self.next_word_end(),
+            EscapeAction::KillWordLeft => self.kill(self.prev_word_start(), self.cursor),
+            EscapeAction::KillWordRight => self.kill(self.cursor, self.next_word_end()),
+            EscapeAction::PasteStart => self.pasting = true,
+            EscapeAction::PasteEnd => self.pasting = false,
+            EscapeAction::History | EscapeAction::Unknown => self.exact = false,
+        }
+    }
+
+    /// Action of a CSI or SS3 sequence, given without its `ESC [` or `ESC O` prefix
+    fn csi_action(seq: &[u8]) -> EscapeAction {
+        match seq {
+            b"D" => EscapeAction::Left,
+            b"C" => EscapeAction::Right,
+            b"H" | b"1~" | b"7~" => EscapeAction::Home,
+            b"F" | b"4~" | b"8~" => EscapeAction::End,
+            b"3~" => EscapeAction::Delete,
+            // Ctrl or Alt with the arrows
+            b"1;5D" | b"1;3D" => EscapeAction::WordLeft,
+            b"1;5C" | b"1;3C" => EscapeAction::WordRight,
+            // Up, Down, Page Up, Page Down
+            b"A" | b"B" | b"5~" | b"6~" => EscapeAction::History,
+            b"200~" => EscapeAction::PasteStart,
+            b"201~" => EscapeAction::PasteEnd,
+            _ => EscapeAction::Unknown,
+        }
     }
 
+    /// Remove and return the character before the cursor
+    pub fn pop_char(&mut self) -> Option<char> {
+        let start = self.prev_char_start();
+        let removed: Vec<u8> = self.buf.drain(start..self.cursor).collect();
+        self.cursor = start;
+        String::from_utf8_lossy(&removed).chars().next()
+    }
+
+    /// Delete the character under the cursor
+    fn delete_char(&mut self) {
+        let end = self.next_char_start();
+        self.buf.drain(self.cursor..end);
+    }
+
+    /// Insert a byte at the cursor
     pub fn push_char(&mut self, c: u8) {
-        self.buf.push(c);
+        self.insert(&[c]);
+    }
+
+    fn insert(&mut self, bytes: &[u8]) {
+        self.buf
+            .splice(self.cursor..self.cursor, bytes.iter().copied());
+        self.cursor += bytes.len();
+    }
+
+    /// Consecutive kills are accumulated in the kill buffer, as readline does
+    fn start_key(&mut self) {
+        self.appending_kill = std::mem::take(&mut self.last_kill);
+    }
+
+    /// Remove `start..end` and keep it to be yanked
+    fn kill(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        let killed: Vec<u8> = self.buf.drain(start..end).collect();
+        if !self.appending_kill {
+            self.kill_buffer = killed;
+        } else if end <= self.cursor {
+            // Killing backward, the text goes before what was already killed
+            self.kill_buffer.splice(0..0, killed);
+        } else {
+            self.kill_buffer.extend(killed);
+        }
+        self.cursor = start;
+        self.last_kill = true;
+    }
+
+    /// Move the cursor to the end of the line, e.g. before pushing the final `\r`
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.buf.len();
     }
 
     pub fn clear(&mut self) {
         self.buf.clear();
+        self.cursor = 0;
+        self.escape.clear();
+        self.pasting = false;
+        self.exact = true;
+    }
+
+    /// Whether a paste is in progress, its line ends are part of the line
+    pub fn is_pasting(&self) -> bool {
+        self.pasting
     }
 
-    /// Count the number of trailing spaces at the end of the buffer
+    /// Whether the line is the one the shell has, without history or completion used
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    fn prev_char_start(&self) -> usize {
+        let mut i = self.cursor.saturating_sub(1);
+        while i > 0 && is_utf8_continuation(self.buf[i]) {
+            i -= 1;
+        }
+        i
+    }
+
+    fn next_char_start(&self) -> usize {
+        let mut i = (self.cursor + 1).min(self.buf.len());
+        while i < self.buf.len() && is_utf8_continuation(self.buf[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Start of the word before the cursor, words being alphanumeric as for Alt+B
+    fn prev_word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !is_word_byte(self.buf[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && is_word_byte(self.buf[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    /// End of the word after the cursor, words being alphanumeric as for Alt+F
+    fn next_word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.buf.len() && !is_word_byte(self.buf[i]) {
+            i += 1;
+        }
+        while i < self.buf.len() && is_word_byte(self.buf[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Count the number of spaces right before the cursor
     fn count_trailing_spaces(&self) -> usize {
-        self.buf.iter().rev().take_while(|&&b| b == b' ').count()
+        self.buf[..self.cursor]
+            .iter()
+            .rev()
+            .take_while(|&&b| b == b' ')
+            .count()
     }
 
-    /// Peek at the last word in the buffer without removing it
+    /// Peek at the word before the cursor without removing it, words being separated by spaces
     pub fn peek_word(&self) -> Option<&[u8]> {
         let trailing_spaces = self.count_trailing_spaces();
-        if self.buf.len() <= trailing_spaces {
+        if self.cursor <= trailing_spaces {
             return None;
         }
 
         // Find the start of the last word
-        let mut start = self.buf.len() - trailing_spaces;
+        let end = self.cursor - trailing_spaces;
+        let mut start = end;
         while start > 0 && self.buf[start - 1] != b' ' {
             start -= 1;
         }
-        Some(&self.buf[start..self.buf.len() - trailing_spaces])
+        Some(&self.buf[start..end])
     }
 
-    /// Remove and return the last word in `Vec<u8>` as it not longer exist in buffer
+    /// Remove and return the word before the cursor in `Vec<u8>` as it not longer exist in buffer
     pub fn pop_word(&mut self) -> Option<Vec<u8>> {
-        let word_slice = self.peek_word()?;
-        let word = word_slice.to_vec();
-
+        let word = self.peek_word()?.to_vec();
         let trailing_spaces = self.count_trailing_spaces();
 
-        // Truncate the buffer to remove the last word + its trailing spaces
-        let new_len = self.buf.len().saturating_sub(word.len() + trailing_spaces);
-        self.buf.truncate(new_len);
-
+        // Remove the word + its trailing spaces
+        self.kill(self.cursor - word.len() - trailing_spaces, self.cursor);
         Some(word)
     }
 
@@ -74,10 +312,44 @@ impl Default for CharBuffer {
     }
 }
 
+enum EscapeAction {
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    WordLeft,
+    WordRight,
+    KillWordLeft,
+    KillWordRight,
+    History,
+    PasteStart,
+    PasteEnd,
+    Unknown,
+}
+
+fn is_utf8_continuation(b: u8) -> bool {
+    b & 0b1100_0000 == 0b1000_0000
+}
+
+/// Non-ASCII bytes are part of words, as readline does for letters in UTF-8 locales
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b >= 0x80
+}
+
 #[cfg(test)]
 mod test {
     use super::CharBuffer;
 
+    /// Feed `keys` to a new buffer and return the line
+    fn edit(keys: &[u8]) -> String {
+        let mut buf = CharBuffer::new();
+        for &c in keys {
+            buf.feed(c);
+        }
+        String::from_utf8(buf.get_buf().to_vec()).unwrap()
+    }
+
     #[test]
     fn test_count_trailing_spaces() {
         let mut buf = CharBuffer::from_vec(b"hello world   ".to_vec());
@@ -91,11 +363,11 @@ mod test {
     }
 
     #[test]
-    fn pop_char_returns_last_byte() {
+    fn pop_char_returns_last_char() {
         let mut buf = CharBuffer::from_vec(vec![b'a', b'b', b'c']);
-        assert_eq!(buf.pop_char(), Some(b'c'));
-        assert_eq!(buf.pop_char(), Some(b'b'));
-        assert_eq!(buf.pop_char(), Some(b'a'));
+        assert_eq!(buf.pop_char(), Some('c'));
+        assert_eq!(buf.pop_char(), Some('b'));
+        assert_eq!(buf.pop_char(), Some('a'));
         assert_eq!(buf.pop_char(), None);
     }
 
@@ -187,4 +459,159 @@ mod test {
         assert_eq!(buf4.peek_word(), None);
         assert_eq!(buf4.pop_word(), None);
     }
+
+    #[test]
+    fn backspace() {
+        assert_eq!(edit(b"lss\x7f -l"), "ls -l");
+        assert_eq!(edit(b"lss\x08"), "ls");
+        assert_eq!(edit(b"\x7fls"), "ls");
+    }
+
+    #[test]
+    fn ctrl_w() {
+        assert_eq!(edit(b"git commit --amend\x17-m"), "git commit -m");
+        // Words are separated by spaces only
+        assert_eq!(edit(b"cd /usr/local/bin\x17/tmp"), "cd /tmp");
+    }
+
+    #[test]
+    fn arrows() {
+        assert_eq!(edit(b"ech hi\x1b[D\x1b[D\x1b[Do"), "echo hi");
+        assert_eq!(
+            edit(b"ls\x1b[D\x1b[D\x1b[D\x1b[C\x1b[C\x1b[C\x1b[C -a"),
+            "ls -a"
+        );
+        // Up and down recall the history, which is not known here
+        assert_eq!(edit(b"ls\x1b[A\x1b[B"), "ls");
+    }
+
+    #[test]
+    fn ctrl_b_and_ctrl_f() {
+        assert_eq!(edit(b"ech hi\x02\x02\x02o"), "echo hi");
+        assert_eq!(edit(b"ls\x02\x02\x06\x06\x06 -a"), "ls -a");
+    }
+
+    #[test]
+    fn home_and_end() {
+        assert_eq!(edit(b"hi\x01echo \x05!"), "echo hi!");
+        assert_eq!(edit(b"hi\x1b[Hecho \x1b[F!"), "echo hi!");
+        assert_eq!(edit(b"hi\x1bOHecho \x1bOF!"), "echo hi!");
+        assert_eq!(edit(b"hi\x1b[1~echo \x1b[4~!"), "echo hi!");
+    }
+
+    #[test]
+    fn delete() {
+        assert_eq!(
+            edit(b"rm -rf /\x01\x1b[3~\x1b[3~\x1b[3~\x1b[3~\x1b[3~\x1b[3~ls"),
+            "ls /"
+        );
+        assert_eq!(edit(b"lsx\x02\x04"), "ls");
+        // Nothing to delete at the end of the line
+        assert_eq!(edit(b"ls\x1b[3~\x04"), "ls");
+    }
+
+    #[test]
+    fn ctrl_u_and_ctrl_k() {
+        assert_eq!(edit(b"rm -rf /\x15ls"), "ls");
+        assert_eq!(edit(b"ls -la /tmp\x02\x02\x02\x02\x02\x0b"), "ls -la");
+        assert_eq!(edit(b"ls\x01\x15"), "ls");
+    }
+
+    #[test]
+    fn ctrl_y_yanks_the_killed_text() {
+        assert_eq!(edit(b"world\x15hello \x19"), "hello world");
+        // Consecutive kills are yanked together
+        assert_eq!(edit(b"echo a b\x17\x17\x19\x19"), "echo a ba b");
+        // A kill after another key starts again
+        assert_eq!(edit(b"echo a b\x17\x1b[D\x17\x19"), "echo a ");
+    }
+
+    #[test]
+    fn alt_b_and_alt_f() {
+        assert_eq!(
+            edit(b"git push origin\x1bb\x1bb-f \x1bf\x1bf main"),
+            "git -f push origin main"
+        );
+        assert_eq!(edit(b"cd /usr/bin\x1bblocal/"), "cd /usr/local/bin");
+        assert_eq!(edit(b"git push\x1b[1;5D-f \x1b[1;5C!"), "git -f push!");
+    }
+
+    #[test]
+    fn alt_d_and_alt_backspace() {
+        assert_eq!(
+            edit(b"cd /usr/local/bin\x1b\x7f\x1b\x7fsbin"),
+            "cd /usr/sbin"
+        );
+        assert_eq!(edit(b"rm old new\x01\x1bf\x1bd"), "rm new");
+    }
+
+    #[test]
+    fn utf8_characters() {
+        assert_eq!(edit("echo été".as_bytes()), "echo été");
+        assert_eq!(edit("echo été\x7f\x7fa".as_bytes()), "echo éa");
+        assert_eq!(edit("echo 日本\x1b[D語\x1b[C!".as_bytes()), "echo 日語本!");
+        assert_eq!(edit("echo 日本\x02\x02\x04".as_bytes()), "echo 本");
+        assert_eq!(
+            edit("echo café crème\x1bb\x1bb\x1bd".as_bytes()),
+            "echo  crème"
+        );
+    }
+
+    #[test]
+    fn other_control_keys_are_ignored() {
+        assert_eq!(edit(b"l\x0cs\x1bx"), "ls");
+    }
+
+    #[test]
+    fn history_and_completion_are_inexact() {
+        for keys in [
+            &b"ls\t-a"[..],
+            b"\x1b[A",
+            b"\x1bOB",
+            b"\x10",
+            b"\x12git",
+            b"echo \x1b.",
+            b"ls -la\x14",
+            b"ls\x1f",
+            b"echo \x16\t",
+            b"ls\x18\x05",
+            b"git commit\x1bt",
+            b"ls\x1bu",
+            b"ls\x1b[15~",
+        ] {
+            let mut buf = CharBuffer::new();
+            for &c in keys {
+                buf.feed(c);
+            }
+            assert!(!buf.is_exact(), "{:?}", keys);
+            buf.clear();
+            assert!(buf.is_exact());
+        }
+        assert_eq!(edit(b"ls\t-a"), "ls\t-a");
+    }
+
+    #[test]
+    fn bracketed_paste() {
+        let mut buf = CharBuffer::new();
+        for &c in b"\x1b[200~echo a\recho b\x1b[201~" {
+            buf.feed(c);
+            if c == b'\r' {
+                assert!(buf.is_pasting());
+            }
+        }
+        assert!(!buf.is_pasting());
+        assert!(buf.is_exact());
+        assert_eq!(buf.get_buf(), b"echo a\necho b");
+    }
+
+    #[test]
+    fn move_to_end() {
+        let mut buf = CharBuffer::new();
+        for &c in b"ls\x01" {
+            buf.feed(c);
+        }
+        buf.move_to_end();
+        buf.push_char(b'\r');
+        assert_eq!(buf.get_buf(), b"ls\r");
+    }
 }
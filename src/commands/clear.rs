@@ -1,6 +1,6 @@
 use super::RunnableCommand;
 use crate::errors::ReplayResult;
-use crate::paths;
+use crate::session::Session;
 use clap::Args;
 
 #[derive(Args, PartialEq, Eq, Debug)]
@@ -8,8 +8,8 @@ pub struct ClearCommand {}
 
 impl RunnableCommand for ClearCommand {
     fn run(&self) -> ReplayResult<()> {
-        paths::clear_replay_dir()?;
-        println!("Sessions cleared");
+        Session::remove_all_sessions()?;
+        println!("Sessions and their names cleared, the config is kept");
         Ok(())
     }
 }
@@ -0,0 +1,54 @@
+//! ConfigCommand: Read or change the default options in the configuration file.
+
+use super::RunnableCommand;
+use crate::config::{self, Config};
+use crate::errors::ReplayResult;
+use clap::{Args, Subcommand};
+use toml::Value;
+
+/// CLI command to manage the configuration file.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(Subcommand, PartialEq, Eq, Debug)]
+enum ConfigAction {
+    /// Print the value of a key, e.g. `run.delay`
+    Get { key: String },
+
+    /// Write the value of a key in the configuration file
+    Set { key: String, value: String },
+
+    /// Print all the keys with their value
+    List,
+}
+
+impl RunnableCommand for ConfigCommand {
+    fn run(&self) -> ReplayResult<()> {
+        match &self.action {
+            ConfigAction::Get { key } => {
+                println!("{}", Self::format_value(&config::get()?.get_value(key)?));
+            }
+            ConfigAction::Set { key, value } => {
+                Config::set_value(&Config::get_path(), key, value)?;
+            }
+            ConfigAction::List => {
+                for (key, value) in config::get()?.list() {
+                    println!("{} = {}", key, Self::format_value(&value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ConfigCommand {
+    fn format_value(value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            value => value.to_string(),
+        }
+    }
+}
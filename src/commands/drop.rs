@@ -1,22 +1,23 @@
 use super::RunnableCommand;
 use crate::args;
 use crate::errors::ReplayResult;
-use crate::session::Session;
+use crate::session::{Session, SessionRef};
 use clap::Args;
 
 #[derive(Args, PartialEq, Eq, Debug)]
 pub struct DropCommand {
+    /// Session in the form replay@{index}, a session name or an id prefix
     #[arg(
         value_name = "session_name",
         default_value = "replay@{0}",
         value_parser = args::parse_session_index
     )]
-    session_index: u32,
+    session: SessionRef,
 }
 
 impl RunnableCommand for DropCommand {
     fn run(&self) -> ReplayResult<()> {
-        Session::remove_session_by_index(self.session_index)?;
+        Session::remove_session_by_index(self.session.resolve_index()?)?;
         Ok(())
     }
 }
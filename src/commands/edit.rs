@@ -0,0 +1,179 @@
+//! EditCommand: Edit the commands and the description of a recorded session.
+
+use super::RunnableCommand;
+use crate::args;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::session::{Session, SessionRef};
+use clap::Args;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+
+const DESCRIPTION_HEADER: &str = "# description:";
+/// Start of the lines continuing a multi-line command.
+/// No command starts with a pipe, unlike `>` which truncates a file.
+const CONTINUATION_PREFIX: &str = "| ";
+
+/// CLI command to edit a session in `$EDITOR`.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct EditCommand {
+    /// Session in the form replay@{index}, a session name or an id prefix
+    #[arg(
+        value_name = "session_name",
+        default_value = "replay@{0}",
+        value_parser = args::parse_session_index
+    )]
+    session: SessionRef,
+
+    /// Only change the description, without opening the editor
+    #[arg(long, short, value_parser = args::parse_session_description)]
+    description: Option<String>,
+}
+
+impl RunnableCommand for EditCommand {
+    fn run(&self) -> ReplayResult<()> {
+        let mut session = Session::load_session_by_index(self.session.resolve_index()?)?;
+        if let Some(desc) = &self.description {
+            args::validate_session_description(desc)?;
+            session.description = Some(desc.clone());
+            session.update_session()?;
+            println!("Session '{}' description updated", self.session);
+            return Ok(());
+        }
+
+        // Created with a random name and only readable by the user, removed when dropped
+        let mut file = tempfile::Builder::new()
+            .prefix("replay-edit-")
+            .suffix(".sh")
+            .tempfile()?;
+        file.write_all(Self::to_file_content(&session).as_bytes())?;
+        file.flush()?;
+        let path = file.path();
+        let edited = Self::open_editor(path).and_then(|_| Ok(std::fs::read_to_string(path)?));
+        file.close()?;
+
+        let (description, commands) = Self::parse_file_content(&edited?)?;
+        if commands.is_empty() {
+            return Err(ReplayError::SessionError(
+                "No command left, session not changed".into(),
+            ));
+        }
+        if description == session.description && commands.iter().eq(session.iter_script_commands())
+        {
+            println!("Session '{}' not changed", self.session);
+            return Ok(());
+        }
+        session.description = description;
+        session.set_script_commands(commands);
+        session.update_session()?;
+        println!("Session '{}' updated", self.session);
+        Ok(())
+    }
+}
+
+impl EditCommand {
+    fn to_file_content(session: &Session) -> String {
+        let mut content = format!(
+            "{} {}\n# One command per line, the other lines starting with '#' are ignored.\n# The next lines of a multi-line command start with '{}'.\n",
+            DESCRIPTION_HEADER,
+            session.description.as_deref().unwrap_or_default(),
+            CONTINUATION_PREFIX
+        );
+        for cmd in session.iter_script_commands() {
+            content.push_str(&cmd.replace('\n', &format!("\n{}", CONTINUATION_PREFIX)));
+            content.push('\n');
+        }
+        content
+    }
+
+    fn parse_file_content(content: &str) -> ReplayResult<(Option<String>, Vec<String>)> {
+        let mut description = None;
+        let mut commands = Vec::new();
+        for line in content.lines() {
+            if let Some(desc) = line.strip_prefix(DESCRIPTION_HEADER) {
+                let desc = desc.trim();
+                if !desc.is_empty() {
+                    description = Some(args::validate_session_description(desc)?);
+                }
+            } else if let Some(next) = line
+                .strip_prefix(CONTINUATION_PREFIX)
+                // Editors may remove the trailing space of an empty line
+                .or((line == CONTINUATION_PREFIX.trim_end()).then_some(""))
+                .filter(|_| !commands.is_empty())
+            {
+                let cmd: &mut String = commands.last_mut().unwrap();
+                cmd.push('\n');
+                cmd.push_str(next);
+            } else if !line.starts_with('#') && !line.trim().is_empty() {
+                commands.push(line.to_string());
+            }
+        }
+        Ok((description, commands))
+    }
+
+    /// Open `path` in `$VISUAL` or `$EDITOR`, which may contain arguments (e.g. `code --wait`)
+    fn open_editor(path: &Path) -> ReplayResult<()> {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| String::from("vi"));
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path)
+            .status()?;
+        if !status.success() {
+            return Err(ReplayError::SessionError(format!(
+                "Editor '{}' exited with {}, session not changed",
+                editor, status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_content_round_trip() {
+        let mut session = Session::new(Some("edit round trip".into())).unwrap();
+        for cmd in [
+            "ls -la\r",
+            "echo 'a  b'\r",
+            "for i in 1 2\ndo echo $i\ndone\r",
+            "exit\r",
+        ] {
+            session.add_command(cmd.into());
+        }
+        let content = EditCommand::to_file_content(&session);
+        assert!(content.starts_with("# description: edit round trip\n"));
+
+        let (description, commands) = EditCommand::parse_file_content(&content).unwrap();
+        assert_eq!(description.as_deref(), Some("edit round trip"));
+        assert_eq!(
+            commands,
+            vec!["ls -la", "echo 'a  b'", "for i in 1 2\ndo echo $i\ndone"]
+        );
+    }
+
+    #[test]
+    fn parse_edited_content() {
+        let content = "# description:\n# a comment\nls\n\n  pwd\n";
+        let (description, commands) = EditCommand::parse_file_content(content).unwrap();
+        assert_eq!(description, None);
+        assert_eq!(commands, vec!["ls", "  pwd"]);
+
+        let content = "cat <<EOF\n| a\n|\n| > b\n| EOF\n> out.log\n";
+        let (_, commands) = EditCommand::parse_file_content(content).unwrap();
+        assert_eq!(commands, vec!["cat <<EOF\na\n\n> b\nEOF", "> out.log"]);
+
+        // The description is validated as on the command line
+        let content = "# description: short\nls\n";
+        assert!(matches!(
+            EditCommand::parse_file_content(content),
+            Err(ReplayError::SessionError(_))
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+//! ExportCommand: Convert a recorded session to another format.
+
+use super::RunnableCommand;
+use crate::args;
+use crate::errors::ReplayResult;
+use crate::export::{export_session, ExportConfig, ExportFormat};
+use crate::session::{Session, SessionRef};
+use clap::Args;
+use std::fs::File;
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+
+/// CLI command to export a recorded session.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct ExportCommand {
+    /// Session in the form replay@{index}, a session name or an id prefix
+    #[arg(
+        value_name = "session_name",
+        default_value = "replay@{0}",
+        value_parser = args::parse_session_index
+    )]
+    session: SessionRef,
+
+    /// Format of the exported session
+    #[arg(long, short, value_enum)]
+    format: ExportFormat,
+
+    /// File to write the export to, stdout if not specified
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Stop the exported script on the first error (`set -euo pipefail`, `set -eu` in sh).
+    /// Not available for fish
+    #[arg(long)]
+    strict: bool,
+}
+
+impl RunnableCommand for ExportCommand {
+    fn run(&self) -> ReplayResult<()> {
+        let session = Session::load_session_by_index(self.session.resolve_index()?)?;
+        let config = ExportConfig {
+            strict: self.strict,
+        };
+        match &self.output {
+            Some(path) => {
+                export_session(&session, self.format, &config, File::create(path)?)?;
+                if self.format == ExportFormat::Sh {
+                    Self::make_executable(path)?;
+                }
+                Ok(())
+            }
+            None => export_session(&session, self.format, &config, stdout().lock()),
+        }
+    }
+}
+
+impl ExportCommand {
+    #[cfg(test)]
+    pub fn new(session: SessionRef, format: ExportFormat, output: Option<PathBuf>) -> Self {
+        Self {
+            session,
+            format,
+            output,
+            strict: false,
+        }
+    }
+
+    #[cfg(unix)]
+    fn make_executable(path: &Path) -> ReplayResult<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        std::fs::set_permissions(path, permissions)?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn make_executable(_path: &Path) -> ReplayResult<()> {
+        Ok(())
+    }
+}
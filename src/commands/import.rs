@@ -0,0 +1,135 @@
+//! ImportCommand: Create a session from a script, a shell history or stdin.
+
+use super::RunnableCommand;
+use crate::args;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::import::{parse_history, parse_script, select_range};
+use crate::session::Session;
+use clap::{value_parser, Args};
+use std::io::{stdin, Read};
+use std::path::PathBuf;
+
+/// CLI command to import a session.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct ImportCommand {
+    /// File to import the commands from, stdin if not specified or `-`
+    #[arg(value_name = "file")]
+    source: Option<PathBuf>,
+
+    /// Parse the source as a bash or zsh history file,
+    /// `$HISTFILE` or `~/.bash_history` if no file is specified
+    #[arg(long)]
+    history: bool,
+
+    /// First entry to import (1-based)
+    #[arg(long, value_name = "n", value_parser = value_parser!(u64).range(1..))]
+    from: Option<u64>,
+
+    /// Last entry to import (1-based, inclusive)
+    #[arg(long, value_name = "n", value_parser = value_parser!(u64).range(1..))]
+    to: Option<u64>,
+
+    /// Description of the imported session
+    #[arg(long, short, value_parser = args::parse_session_description)]
+    description: Option<String>,
+
+    /// Disable default file compression
+    #[arg(long)]
+    no_compression: bool,
+}
+
+impl RunnableCommand for ImportCommand {
+    fn run(&self) -> ReplayResult<()> {
+        if let Some(desc) = &self.description {
+            args::validate_session_description(desc)?;
+        }
+        let content = self.read_source()?;
+        let commands = if self.history {
+            parse_history(&content)
+        } else {
+            parse_script(&content)
+        };
+        let commands = select_range(
+            commands,
+            self.from.map(|n| n as usize),
+            self.to.map(|n| n as usize),
+        );
+        if commands.is_empty() {
+            return Err(ReplayError::SessionError("No command to import".into()));
+        }
+
+        let session = Self::build_session(commands, self.description.clone())?;
+        session.save_session(!self.no_compression)?;
+        println!(
+            "Imported {} commands in replay@{{0}}",
+            session.iter_script_commands().count()
+        );
+        Ok(())
+    }
+}
+
+impl ImportCommand {
+    fn read_source(&self) -> ReplayResult<String> {
+        let path = match &self.source {
+            Some(path) if path.as_os_str() != "-" => Some(path.clone()),
+            Some(_) => None,
+            None if self.history => Some(Self::default_history_path()?),
+            None => None,
+        };
+
+        let mut raw = Vec::new();
+        match path {
+            Some(path) => raw = std::fs::read(path)?,
+            None => {
+                stdin().read_to_end(&mut raw)?;
+            }
+        }
+        // History files are not guaranteed to be valid UTF-8 (e.g. zsh metafied bytes)
+        Ok(String::from_utf8_lossy(&raw).to_string())
+    }
+
+    fn default_history_path() -> ReplayResult<PathBuf> {
+        if let Some(path) = std::env::var_os("HISTFILE") {
+            return Ok(PathBuf::from(path));
+        }
+        let home = dirs::home_dir().ok_or_else(|| {
+            ReplayError::SessionError(String::from(
+                "No home directory to find the history in, give the history file",
+            ))
+        })?;
+        Ok(home.join(".bash_history"))
+    }
+
+    /// Build a session typed the same way `handle_user_input` records it:
+    /// each command ends with `\r` and the session ends with `exit`
+    fn build_session(commands: Vec<String>, description: Option<String>) -> ReplayResult<Session> {
+        let mut session = Session::new(description)?;
+        for cmd in commands {
+            session.add_command(format!("{}\r", cmd).into_bytes());
+        }
+        if session.get_last_command().map(|cmd| cmd.trim()) != Some("exit") {
+            session.add_command(b"exit\r".to_vec());
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imported_session_ends_with_exit() {
+        let session = ImportCommand::build_session(vec!["ls".into(), "pwd".into()], None).unwrap();
+        assert_eq!(
+            session.iter_commands().collect::<Vec<_>>(),
+            vec!["ls\r", "pwd\r", "exit\r"]
+        );
+
+        let session = ImportCommand::build_session(vec!["ls".into(), "exit".into()], None).unwrap();
+        assert_eq!(
+            session.iter_commands().collect::<Vec<_>>(),
+            vec!["ls\r", "exit\r"]
+        );
+    }
+}
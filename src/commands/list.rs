@@ -1,29 +1,130 @@
 use super::RunnableCommand;
+use crate::args;
+use crate::config;
 use crate::errors::ReplayResult;
 use crate::session::DisplayMeta;
-use crate::session::Session;
-use clap::Args;
+use crate::session::{Session, SessionNameFile};
+use chrono::{DateTime, Utc};
+use clap::{Args, ValueEnum};
+use serde_json::json;
 
-#[derive(Args, PartialEq, Eq, Debug)]
-pub struct ListCommand {}
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ListFormat {
+    /// Human readable list
+    #[default]
+    Text,
+    /// JSON array of sessions
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// Tab separated values, with a header line
+    Tsv,
+}
+
+#[derive(Args, Default, PartialEq, Eq, Debug)]
+pub struct ListCommand {
+    /// Output format
+    #[arg(long, short, value_enum, default_value_t = ListFormat::Text)]
+    format: ListFormat,
+
+    /// Maximum number of sessions to list
+    #[arg(long, short = 'n', value_name = "n")]
+    limit: Option<usize>,
+
+    /// Only list the sessions recorded since a date (`2024-05-01`, RFC 3339)
+    /// or a duration (`30m`, `12h`, `7d`)
+    #[arg(long, value_name = "date", value_parser = args::parse_since)]
+    since: Option<DateTime<Utc>>,
+
+    /// Only list the sessions recorded by a user
+    #[arg(long, short, value_name = "user")]
+    user: Option<String>,
+}
 
 impl RunnableCommand for ListCommand {
     fn run(&self) -> ReplayResult<()> {
-        for session_infos in Self::list()? {
-            println!("{}", session_infos?)
+        let sessions = self.list()?;
+        match self.format {
+            ListFormat::Text => {
+                for session in sessions {
+                    println!("{}", session?)
+                }
+            }
+            ListFormat::Json => {
+                let sessions = sessions
+                    .map(|session| Ok(Self::to_json(&session?)))
+                    .collect::<ReplayResult<Vec<_>>>()?;
+                println!("{}", serde_json::to_string_pretty(&sessions)?);
+            }
+            ListFormat::Jsonl => {
+                for session in sessions {
+                    println!("{}", Self::to_json(&session?));
+                }
+            }
+            ListFormat::Tsv => {
+                println!("index\tid\ttimestamp\tuser\tcommand_count\tdescription\tfirst_commands");
+                for session in sessions {
+                    println!("{}", Self::to_tsv(&session?));
+                }
+            }
         }
         Ok(())
     }
 }
 
 impl ListCommand {
-    fn list() -> ReplayResult<impl Iterator<Item = ReplayResult<String>>> {
-        Ok(Session::get_all_session_metadata()?.enumerate().map(
-            |(i, metadata)| -> ReplayResult<String> {
+    fn list(&self) -> ReplayResult<impl Iterator<Item = ReplayResult<DisplayMeta>> + '_> {
+        let truncate = config::get()?.list.truncate;
+        Ok(Session::get_all_session_metadata()?
+            .enumerate()
+            .map(move |(i, metadata)| -> ReplayResult<DisplayMeta> {
                 let md = metadata?;
-                Ok(DisplayMeta { index: i, meta: md }.to_string())
-            },
-        ))
+                let names = SessionNameFile::get_names(&md.id)?;
+                Ok(DisplayMeta {
+                    index: i,
+                    names,
+                    truncate,
+                    meta: md,
+                })
+            })
+            // Sessions are listed from the most recent, the older ones can be skipped
+            .take_while(|session| match (session, self.since) {
+                (Ok(session), Some(since)) => session.meta.timestamp >= since,
+                _ => true,
+            })
+            .filter(|session| match (session, &self.user) {
+                (Ok(session), Some(user)) => &session.meta.user == user,
+                _ => true,
+            })
+            .take(self.limit.unwrap_or(usize::MAX)))
+    }
+
+    fn to_json(session: &DisplayMeta) -> serde_json::Value {
+        json!({
+            "index": session.index,
+            "id": session.meta.id,
+            "names": session.names,
+            "description": session.meta.description,
+            "timestamp": session.meta.timestamp.to_rfc3339(),
+            "user": session.meta.user,
+            "command_count": session.meta.command_count,
+            "first_commands": session.meta.first_commands,
+        })
+    }
+
+    fn to_tsv(session: &DisplayMeta) -> String {
+        // Tabs and line breaks would break the columns
+        let escape = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+        [
+            session.index.to_string(),
+            session.meta.id.clone(),
+            session.meta.timestamp.to_rfc3339(),
+            escape(&session.meta.user),
+            session.meta.command_count.to_string(),
+            escape(session.meta.description.as_deref().unwrap_or_default()),
+            escape(&session.meta.first_commands.join(" | ")),
+        ]
+        .join("\t")
     }
 }
 
@@ -49,8 +150,11 @@ mod tests {
         ))
         .unwrap();
         session_3.save_session(true).unwrap();
-        let list_output: Vec<_> = ListCommand::list()
+        SessionNameFile::set_name("second", &session_2.id, false).unwrap();
+        let list_output: Vec<_> = ListCommand::default()
+            .list()
             .unwrap()
+            .map(|session| session.map(|session| session.to_string()))
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
         let re1 = Regex::new(
@@ -58,9 +162,66 @@ mod tests {
         )
         .unwrap();
         assert!(re1.is_match(&list_output[0]));
-        let re2 = Regex::new(r"^replay@\{1\}: \d+ seconds ago, message: test session 2$").unwrap();
+        let re2 =
+            Regex::new(r"^replay@\{1\} \(second\): \d+ seconds ago, message: test session 2$")
+                .unwrap();
         assert!(re2.is_match(&list_output[1]));
         let re3 = Regex::new(r"^replay@\{2\}: \d+ seconds ago, commands: ls | echo test$").unwrap();
         assert!(re3.is_match(&list_output[2]));
     }
+
+    #[test]
+    #[serial]
+    fn test_list_filters_and_formats() {
+        setup();
+        let mut session_1 = Session::new(Some("first\tsession".into())).unwrap();
+        for cmd in ["ls\r", "pwd\r", "echo 1\r", "exit\r"] {
+            session_1.add_command(cmd.into());
+        }
+        session_1.save_session(true).unwrap();
+        let session_2 = Session::new(None).unwrap();
+        session_2.save_session(false).unwrap();
+
+        let list = |command: ListCommand| -> Vec<DisplayMeta> {
+            command
+                .list()
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        assert_eq!(list(ListCommand::default()).len(), 2);
+        let limited = list(ListCommand {
+            limit: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].meta.id, session_2.id);
+        assert!(list(ListCommand {
+            user: Some("nobody-replay-test".into()),
+            ..Default::default()
+        })
+        .is_empty());
+        assert!(list(ListCommand {
+            since: Some(Utc::now() + chrono::TimeDelta::hours(1)),
+            ..Default::default()
+        })
+        .is_empty());
+
+        let first = &list(ListCommand::default())[1];
+        assert_eq!(first.meta.command_count, 3);
+        let json = ListCommand::to_json(first);
+        assert_eq!(json["index"], 1);
+        assert_eq!(json["id"], session_1.id);
+        assert_eq!(json["command_count"], 3);
+        assert_eq!(json["first_commands"], json!(["ls", "pwd"]));
+        assert_eq!(
+            ListCommand::to_tsv(first),
+            format!(
+                "1\t{}\t{}\t{}\t3\tfirst session\tls | pwd",
+                session_1.id,
+                session_1.timestamp.to_rfc3339(),
+                session_1.user
+            )
+        );
+    }
 }
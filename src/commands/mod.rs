@@ -7,10 +7,18 @@ use crate::errors::ReplayResult;
 
 // Add commands mod below using pub mod ...
 pub mod clear;
+pub mod config;
 pub mod drop;
+pub mod edit;
+pub mod export;
+pub mod import;
 pub mod list;
+pub mod param;
 pub mod record;
 pub mod run;
+pub mod search;
+pub mod show;
+pub mod tag;
 
 /// This trait is the common runner trait
 pub trait RunnableCommand {
@@ -0,0 +1,45 @@
+//! ParamCommand: Turn values of a recorded session into parameters.
+
+use super::RunnableCommand;
+use crate::args;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::session::{Session, SessionRef};
+use clap::Args;
+
+/// CLI command to replace values in the commands of a session by `{{name}}` placeholders.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct ParamCommand {
+    /// Session in the form replay@{index}, a session name or an id prefix
+    #[arg(value_name = "session_name", value_parser = args::parse_session_index)]
+    session: SessionRef,
+
+    /// Value to replace by the `{{name}}` placeholder, e.g. `env=staging`
+    #[arg(value_name = "name=value", required = true, value_parser = args::parse_parameter)]
+    parameters: Vec<(String, String)>,
+}
+
+impl RunnableCommand for ParamCommand {
+    fn run(&self) -> ReplayResult<()> {
+        let mut session = Session::load_session_by_index(self.session.resolve_index()?)?;
+        if session.is_raw() {
+            return Err(ReplayError::SessionError(String::from(
+                "Sessions recorded with --raw replay their keys, they can't have parameters",
+            )));
+        }
+        for (name, value) in &self.parameters {
+            if session.mark_parameter(name, value) == 0 {
+                return Err(ReplayError::SessionError(format!(
+                    "Value '{}' not found in the commands of session '{}'",
+                    value, self.session
+                )));
+            }
+        }
+        session.update_session()?;
+        println!(
+            "Session '{}' parameters: {}",
+            self.session,
+            session.parameters.join(", ")
+        );
+        Ok(())
+    }
+}
@@ -1,59 +1,174 @@
-use std::io::{stdin, stdout};
+use std::io::stdout;
 
 use super::RunnableCommand;
-use crate::errors::ReplayResult;
-use crate::pty::{run_internal, RecordConfig};
+use crate::args;
+use crate::config::{self, RecordDefaults};
+use crate::errors::{ReplayError, ReplayResult};
+use crate::pty::{run_internal, RecordConfig, TerminalReader};
+use crate::session::SessionNameFile;
+use crate::shell::Shell;
 use clap::Args;
 
 #[derive(Args, PartialEq, Eq, Debug)]
 pub struct RecordCommand {
-    #[arg(value_parser=RecordCommand::validate_session_description)]
+    #[arg(value_parser = args::parse_session_description)]
     session_description: Option<String>,
 
-    /// Disable default file compression
-    #[arg(long)]
+    /// Disable default file compression, also enabled by `record.no_compression` in the config
+    #[arg(long, overrides_with = "compression")]
     no_compression: bool,
+
+    /// Compress the session file even if `record.no_compression` is set in the config
+    #[arg(long, overrides_with = "no_compression")]
+    compression: bool,
+
+    /// Prompt for the session description once the recording is over
+    #[arg(short, long)]
+    edit: bool,
+
+    /// Also record the output printed by each command, also enabled by `record.with_output` in the config
+    #[arg(long, overrides_with = "no_with_output")]
+    with_output: bool,
+
+    /// Don't record the output even if `record.with_output` is set in the config
+    #[arg(long, overrides_with = "with_output")]
+    no_with_output: bool,
+
+    /// Name of the session, to run it with `replay run <name>`
+    #[arg(long, short, value_parser = args::validate_session_name)]
+    name: Option<String>,
+
+    /// Save every occurrence of `value` in the commands as a `{{name}}` parameter,
+    /// to set with `replay run --set name=value`
+    #[arg(long = "param", value_name = "name=value", value_parser = args::parse_parameter)]
+    parameters: Vec<(String, String)>,
+
+    /// Environment variable to save with the session and set when replaying it,
+    /// in addition to `record.env` from the config
+    #[arg(long = "env", value_name = "NAME", value_parser = args::validate_env_name)]
+    env: Vec<String>,
+
+    /// Record the keys as they are typed, with their timing, to replay interactive
+    /// programs (vim, `git add -p`, fzf...) faithfully. They are sent to the shell at once,
+    /// even while a command runs
+    #[arg(long, conflicts_with = "parameters")]
+    raw: bool,
+
+    /// Shell to record the session in, it is also used to replay it.
+    /// Defaults to `record.shell` from the config (bash)
+    #[arg(long, value_enum)]
+    shell: Option<Shell>,
 }
 impl RunnableCommand for RecordCommand {
     fn run(&self) -> ReplayResult<()> {
-        let reader = stdin();
+        if let Some(desc) = &self.session_description {
+            args::validate_session_description(desc)?;
+        }
+        if let Some(name) = &self.name {
+            // Fail before recording rather than losing the session name at the end
+            if SessionNameFile::contains(name)? {
+                return Err(ReplayError::SessionError(format!(
+                    "Session name '{}' already exists",
+                    name
+                )));
+            }
+        }
+        let config = &config::get()?.record;
+        // The keys typed while a command runs are read without the buffer of `stdin()`
+        let reader = TerminalReader;
         let writer = stdout();
         run_internal(
             reader,
             writer,
             RecordConfig {
                 record_input: true,
+                session_description: self.session_description.clone(),
+                no_compression: self.no_compression(config),
+                edit_description: self.edit,
+                record_output: self.with_output(config),
+                session_name: self.name.clone(),
+                shell: self.shell.unwrap_or(config.shell),
+                raw: self.raw,
+                parameters: self.parameters.clone(),
+                cwd: std::env::current_dir().ok(),
+                env: config
+                    .env
+                    .iter()
+                    .chain(&self.env)
+                    .filter_map(|name| Some((name.clone(), std::env::var(name).ok()?)))
+                    .collect(),
                 ..Default::default()
             },
-        )
+        )?;
+        Ok(())
     }
 }
 
 impl RecordCommand {
+    // The flags given on the command line win over the config
+    fn no_compression(&self, config: &RecordDefaults) -> bool {
+        self.no_compression || (config.no_compression && !self.compression)
+    }
+
+    fn with_output(&self, config: &RecordDefaults) -> bool {
+        self.with_output || (config.with_output && !self.no_with_output)
+    }
+
     #[cfg(test)]
-    pub fn new(desc: Option<String>, no_compression: bool) -> Self {
+    pub fn new(desc: Option<String>, no_compression: bool, edit: bool) -> Self {
         RecordCommand {
             session_description: desc,
             no_compression,
+            compression: false,
+            edit,
+            with_output: false,
+            no_with_output: false,
+            name: None,
+            parameters: Vec::new(),
+            env: Vec::new(),
+            shell: None,
+            raw: false,
         }
     }
+}
 
-    fn validate_session_description(s: &str) -> Result<String, String> {
-        if s.len() < 10 {
-            return Err(String::from(
-                "Session description is too short (min 10 chars)",
-            ));
-        }
-        if s.len() > 80 {
-            return Err(String::from(
-                "Session description is too long (max 80 chars)",
-            ));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{parse_command, CliCommand};
 
-        if s.parse::<i32>().is_ok() {
-            return Err(String::from("Session description cannot be an integer"));
+    fn parse(flags: &[&str]) -> RecordCommand {
+        let args: Vec<String> = ["replay", "record"]
+            .iter()
+            .chain(flags)
+            .map(|arg| arg.to_string())
+            .collect();
+        match parse_command(&args).unwrap() {
+            CliCommand::Record(cmd) => cmd,
+            cmd => panic!("Unexpected command {:?}", cmd),
         }
+    }
+
+    #[test]
+    fn flags_win_over_the_config() {
+        let config = RecordDefaults {
+            no_compression: true,
+            with_output: true,
+            ..Default::default()
+        };
+        let cmd = parse(&[]);
+        assert!(cmd.no_compression(&config));
+        assert!(cmd.with_output(&config));
+
+        let cmd = parse(&["--compression", "--no-with-output"]);
+        assert!(!cmd.no_compression(&config));
+        assert!(!cmd.with_output(&config));
+        assert!(!cmd.no_compression(&RecordDefaults::default()));
 
-        Ok(String::from(s))
+        // The last flag given wins
+        let cmd = parse(&["--compression", "--no-compression"]);
+        assert!(cmd.no_compression(&RecordDefaults::default()));
+        let cmd = parse(&["--no-with-output", "--with-output"]);
+        assert!(cmd.with_output(&config));
     }
 }
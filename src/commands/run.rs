@@ -1,47 +1,168 @@
 //! RunCommand: Replay a recorded session with optional delay and dry-run.
 
 use super::RunnableCommand;
-use crate::args;
-use crate::errors::ReplayResult;
-use crate::pty::{run_internal, RawModeReader, RecordConfig};
-use crate::session::Session;
+use crate::args::{self, Speed};
+use crate::config;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::pty::{run_internal, CommandStatus, RawModeReader, RecordConfig, TerminalReader};
+use crate::session::{Session, SessionRef};
+use crate::step_reader::StepReader;
 use clap::{value_parser, Args};
-use std::io::stdout;
+use std::collections::{btree_map::Entry, BTreeMap};
+use std::fs::File;
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Shortest delay between two characters typed in the shell
+const MIN_DELAY: Duration = Duration::from_millis(10);
 
 /// CLI command to run a recorded session.
 #[derive(Args, PartialEq, Eq, Debug)]
 pub struct RunCommand {
-    /// Session name in the form replay@{index}
+    /// Session in the form replay@{index}, a session name or an id prefix
     #[arg(
         value_name = "session_name",
         default_value = "replay@{0}",
         value_parser = args::parse_session_index
     )]
-    session_index: u32,
+    session: SessionRef,
 
     /// Show commands without executing them
     #[arg(short, long)]
     show: bool,
 
+    /// Pause before each command: Enter to run it, `s` to skip it, `e` to edit it, `q` to quit
+    #[arg(long, conflicts_with = "show")]
+    step: bool,
+
+    /// Stop at the first failing command and print the exit status of each command
+    #[arg(long, conflicts_with = "show")]
+    fail_fast: bool,
+
+    /// Run without a terminal (e.g. in CI) and exit with the exit code of the shell
+    #[arg(long, conflicts_with_all = ["show", "step"])]
+    headless: bool,
+
+    /// File to write the output of the shell to, instead of stdout
+    #[arg(long, value_name = "file", requires = "headless")]
+    log: Option<PathBuf>,
+
+    /// Value of a session parameter, the missing ones are prompted for
+    #[arg(long = "set", value_name = "name=value", value_parser = args::parse_parameter)]
+    parameters: Vec<(String, String)>,
+
+    /// Run in the current directory instead of the one the session was recorded in
+    #[arg(long)]
+    here: bool,
+
+    /// Environment variable to set, overriding the value saved with the session
+    #[arg(long = "env", value_name = "NAME=value", value_parser = args::parse_env_var)]
+    env: Vec<(String, String)>,
+
+    /// Type the commands with the rhythm they were recorded with.
+    /// Sessions recorded with `--raw` are always replayed this way
+    #[arg(long, conflicts_with_all = ["show", "step"])]
+    realtime: bool,
+
+    /// Speed factor of the realtime replay, e.g. `2x` or `0.5x`
+    #[arg(long, value_name = "factor", requires = "realtime", value_parser = args::parse_speed)]
+    speed: Option<Speed>,
+
+    /// Longest pause of the realtime replay, e.g. `2s` or `500ms`
+    #[arg(long, value_name = "duration", requires = "realtime", value_parser = args::parse_duration)]
+    max_idle: Option<Duration>,
+
     /// Delay in milliseconds between each character during replay typing.
-    /// Must be at least 10 ms.
-    #[arg(long, short, default_value_t = 10, value_name = "ms", value_parser = value_parser!(u64).range(10..))]
-    delay: u64,
+    /// Must be at least 10 ms, defaults to `run.delay` from the config (10 ms).
+    #[arg(long, short, value_name = "ms", value_parser = value_parser!(u64).range(10..))]
+    delay: Option<u64>,
 }
 
 impl RunnableCommand for RunCommand {
     fn run(&self) -> ReplayResult<()> {
-        let session: Session = Session::load_session_by_index(self.session_index)?;
+        let mut session: Session = Session::load_session_by_index(self.session.resolve_index()?)?;
         if self.show {
-            self.show_commands(session)?;
+            return self.show_commands(session);
+        }
+        if self.step && session.is_raw() {
+            return Err(ReplayError::SessionError(String::from(
+                "Sessions recorded with --raw can't be replayed step by step",
+            )));
+        }
+        // The keys of a raw session are replayed as they were typed, without the commands
+        if session.is_raw() {
+            if !self.parameters.is_empty() {
+                return Err(ReplayError::SessionError(String::from(
+                    "Sessions recorded with --raw replay their keys, --set can't change them",
+                )));
+            }
         } else {
-            let commands: String = session.iter_commands().collect();
-            let input = RawModeReader::with_input_and_delay(
-                commands.as_bytes(),
-                std::time::Duration::from_millis(self.delay),
+            let values = self.parameter_values(&session)?;
+            session.substitute_parameters(&values)?;
+        }
+
+        let config = RecordConfig {
+            fail_fast: self.fail_fast,
+            headless: self.headless,
+            shell: session.shell,
+            cwd: self.working_dir(&session)?,
+            env: session
+                .env
+                .clone()
+                .into_iter()
+                .chain(self.env.clone())
+                .collect(),
+            size: session.size,
+            raw: session.is_raw(),
+            ..Default::default()
+        };
+        let delay = Duration::from_millis(match self.delay {
+            Some(delay) => delay,
+            None => config::get()?.run.delay,
+        });
+        let outcome = if self.step {
+            let input = StepReader::new(
+                TerminalReader,
+                stdout(),
+                session.iter_script_commands(),
+                delay,
             );
-            let output = stdout();
-            run_internal(input, output, RecordConfig::default())?;
+            run_internal(input, stdout(), config)?
+        } else {
+            let commands: String = session.iter_commands().collect();
+            let input = if session.is_raw() {
+                let keys: Vec<u8> = session
+                    .input
+                    .iter()
+                    .flat_map(|chunk| chunk.data.clone())
+                    .collect();
+                RawModeReader::with_input_and_delays(&keys, self.raw_delays(&session))
+            } else if self.realtime {
+                RawModeReader::with_input_and_delays(
+                    commands.as_bytes(),
+                    self.realtime_delays(&session, delay),
+                )
+            } else {
+                RawModeReader::with_input_and_delay(commands.as_bytes(), delay)
+            };
+            match &self.log {
+                Some(path) => run_internal(input, File::create(path)?, config)?,
+                None => run_internal(input, stdout(), config)?,
+            }
+        };
+
+        if self.fail_fast {
+            Self::print_summary(&outcome.statuses);
+            if let Some(failed) = outcome.statuses.iter().find(|s| s.failed()) {
+                return Err(ReplayError::CommandFailed {
+                    command: failed.command.clone(),
+                    status: failed.status.unwrap_or_default(),
+                });
+            }
+        }
+        if self.headless && outcome.exit_code != 0 {
+            return Err(ReplayError::ShellExit(outcome.exit_code as i32));
         }
         Ok(())
     }
@@ -49,24 +170,282 @@ impl RunnableCommand for RunCommand {
 
 impl RunCommand {
     #[cfg(test)]
-    pub fn new(session_index: u32, show: bool, delay: u64) -> Self {
+    pub fn new(session: SessionRef, show: bool, delay: Option<u64>) -> Self {
         Self {
-            session_index,
+            session,
             show,
+            step: false,
+            fail_fast: false,
+            headless: false,
+            log: None,
+            realtime: false,
+            speed: None,
+            max_idle: None,
+            parameters: Vec::new(),
+            here: false,
+            env: Vec::new(),
             delay,
         }
     }
 
+    /// Delay before each byte of the commands, as they were typed while recording.
+    /// Commands recorded without timing are typed with `delay` between each character.
+    fn realtime_delays(&self, session: &Session, delay: Duration) -> Vec<Duration> {
+        let speed = self.speed.unwrap_or_default();
+        let max_idle = self.max_idle.unwrap_or(Duration::MAX);
+        let mut delays = Vec::new();
+        for (i, cmd) in session.iter_commands().enumerate() {
+            let len = cmd.len();
+            match session.get_command_timing(i) {
+                Some(timing) if len > 0 => {
+                    // The idle time before the first key, then the rhythm of the typed keys
+                    delays.push(Duration::from_millis(timing.idle_ms()));
+                    delays.extend(
+                        timing
+                            .typing_pauses_ms(len - 1)
+                            .into_iter()
+                            .map(Duration::from_millis),
+                    );
+                }
+                _ => delays.extend(std::iter::repeat_n(delay, len)),
+            }
+        }
+        delays
+            .into_iter()
+            .map(|d| speed.apply(d).min(max_idle).max(MIN_DELAY))
+            .collect()
+    }
+
+    /// Delay before each key recorded with `--raw`, as it was typed while recording.
+    /// The keys typed at the same time are sent together.
+    fn raw_delays(&self, session: &Session) -> Vec<Duration> {
+        let speed = self.speed.unwrap_or_default();
+        let max_idle = self.max_idle.unwrap_or(Duration::MAX);
+        let mut previous_ms = 0;
+        let mut delays = Vec::new();
+        // An empty chunk, only found in an edited session file, has no key to delay
+        for chunk in session.input.iter().filter(|chunk| !chunk.data.is_empty()) {
+            let pause = Duration::from_millis(chunk.time_ms.saturating_sub(previous_ms));
+            delays.push(speed.apply(pause).min(max_idle));
+            delays.extend(std::iter::repeat_n(Duration::ZERO, chunk.data.len() - 1));
+            previous_ms = chunk.time_ms;
+        }
+        delays
+    }
+
+    /// Directory the session was recorded in, unless `--here` is given
+    fn working_dir(&self, session: &Session) -> ReplayResult<Option<PathBuf>> {
+        if self.here {
+            return Ok(Some(std::env::current_dir()?));
+        }
+        match &session.cwd {
+            Some(cwd) => {
+                if !cwd.is_dir() {
+                    return Err(ReplayError::SessionError(format!(
+                        "Directory '{}' the session was recorded in does not exist, use --here to run it in the current one",
+                        cwd.display()
+                    )));
+                }
+                Ok(Some(cwd.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Values given with `--set`, the missing ones are asked to the user
+    fn parameter_values(&self, session: &Session) -> ReplayResult<BTreeMap<String, String>> {
+        let expected = &session.parameters;
+        let mut values = BTreeMap::new();
+        for (name, value) in &self.parameters {
+            if !expected.contains(name) {
+                return Err(ReplayError::SessionError(format!(
+                    "Session '{}' has no parameter '{}'",
+                    self.session, name
+                )));
+            }
+            values.insert(name.clone(), value.clone());
+        }
+        // Nobody can answer in headless mode, substitution reports the missing ones
+        if self.headless {
+            return Ok(values);
+        }
+        for name in expected {
+            if let Entry::Vacant(entry) = values.entry(name.clone()) {
+                print!("Value for {{{{{}}}}}: ", entry.key());
+                stdout().flush()?;
+                let mut value = String::new();
+                stdin().read_line(&mut value)?;
+                entry.insert(value.trim_end_matches(['\r', '\n']).to_string());
+            }
+        }
+        Ok(values)
+    }
+
+    fn print_summary(statuses: &[CommandStatus]) {
+        println!("{:>4}  {:>6}  Command", "#", "Status");
+        for (i, status) in statuses.iter().enumerate() {
+            let code = status
+                .status
+                .map_or_else(|| String::from("?"), |code| code.to_string());
+            let interrupted = if status.interrupted {
+                "  (interrupted)"
+            } else {
+                ""
+            };
+            println!(
+                "{:>4}  {:>6}  {}{}",
+                i + 1,
+                code,
+                status.command,
+                interrupted
+            );
+        }
+        if statuses.last().is_some_and(CommandStatus::failed) {
+            println!("Stopped at the first failing command");
+        }
+    }
+
     fn show_commands(&self, session: Session) -> ReplayResult<()> {
-        println!("Commands for session 'replay@{{{}}}':", self.session_index);
-        // The last command is always "exit", so we skip printing it
-        for (i, cmd) in session
-            .iter_commands()
-            .take(session.iter_commands().count() - 1)
-            .enumerate()
-        {
-            println!("  {}. {}", i + 1, cmd);
+        let parameters = &session.parameters;
+        if !parameters.is_empty() {
+            println!("Parameters: {}", parameters.join(", "));
+        }
+        println!("Commands for session '{}':", self.session);
+        for (i, cmd) in session.iter_script_commands().enumerate() {
+            println!("  {}. {}", i + 1, cmd.replace('\n', "\n     "));
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::setup;
+    use crate::session::{CommandTiming, InputChunk};
+    use serial_test::serial;
+
+    #[test]
+    fn realtime_delays_follow_recorded_timing() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command(b"ls\r".to_vec());
+        session.set_command_timing(CommandTiming {
+            prompt_ms: 100,
+            typed_ms: 3100,
+            entered_ms: 3500,
+            ..Default::default()
+        });
+        session.add_command(b"exit\r".to_vec());
+
+        let ms = Duration::from_millis;
+        let mut command = RunCommand::new(SessionRef::Index(0), false, None);
+        command.realtime = true;
+        assert_eq!(
+            command.realtime_delays(&session, ms(20)),
+            vec![
+                ms(3000),
+                ms(200),
+                ms(200),
+                ms(20),
+                ms(20),
+                ms(20),
+                ms(20),
+                ms(20)
+            ]
+        );
+
+        command.speed = Some(args::parse_speed("2x").unwrap());
+        command.max_idle = Some(ms(1000));
+        assert_eq!(
+            command.realtime_delays(&session, ms(20)),
+            vec![
+                ms(1000),
+                ms(100),
+                ms(100),
+                ms(10),
+                ms(10),
+                ms(10),
+                ms(10),
+                ms(10)
+            ]
+        );
+    }
+
+    #[test]
+    fn realtime_delays_follow_typed_keys() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command(b"ls\r".to_vec());
+        session.set_command_timing(CommandTiming {
+            prompt_ms: 100,
+            typed_ms: 1100,
+            entered_ms: 1500,
+            keys_ms: vec![0, 100, 400],
+            ..Default::default()
+        });
+        // `lx`, Backspace, `s` and Enter
+        session.add_command(b"ls\r".to_vec());
+        session.set_command_timing(CommandTiming {
+            prompt_ms: 2000,
+            typed_ms: 2000,
+            entered_ms: 2900,
+            keys_ms: vec![0, 100, 200, 700, 900],
+            ..Default::default()
+        });
+
+        let ms = Duration::from_millis;
+        let command = RunCommand::new(SessionRef::Index(0), false, None);
+        assert_eq!(
+            command.realtime_delays(&session, ms(20)),
+            vec![ms(1000), ms(100), ms(300), ms(10), ms(150), ms(750)]
+        );
+    }
+
+    #[test]
+    fn raw_delays_follow_recorded_keys() {
+        let mut session = Session::new(None).unwrap();
+        for (time_ms, byte) in [(500, b'v'), (500, b'i'), (700, b'\r'), (4700, b':')] {
+            session.add_input(time_ms, byte);
+        }
+
+        let ms = Duration::from_millis;
+        let mut command = RunCommand::new(SessionRef::Index(0), false, None);
+        assert_eq!(
+            command.raw_delays(&session),
+            vec![ms(500), ms(0), ms(200), ms(4000)]
+        );
+
+        command.speed = Some(args::parse_speed("2x").unwrap());
+        command.max_idle = Some(ms(1000));
+        assert_eq!(
+            command.raw_delays(&session),
+            vec![ms(250), ms(0), ms(100), ms(1000)]
+        );
+
+        session.input.insert(
+            1,
+            InputChunk {
+                time_ms: 600,
+                data: Vec::new(),
+            },
+        );
+        assert_eq!(
+            command.raw_delays(&session),
+            vec![ms(250), ms(0), ms(100), ms(1000)]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn raw_session_rejects_parameters() {
+        setup();
+        let mut session = Session::new(None).unwrap();
+        session.add_command(b"vi\r".to_vec());
+        session.add_input(0, b'v');
+        session.save_session(false).unwrap();
+
+        let mut command = RunCommand::new(SessionRef::Index(0), false, None);
+        command.headless = true;
+        command.parameters = vec![("env".into(), "staging".into())];
+        assert!(matches!(command.run(), Err(ReplayError::SessionError(_))));
+    }
+}
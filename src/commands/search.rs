@@ -0,0 +1,124 @@
+//! SearchCommand: Find the sessions containing a pattern.
+
+use super::RunnableCommand;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::session::search::SearchMatch;
+use crate::session::{Session, SessionNameFile};
+use clap::Args;
+use crossterm::style::Stylize;
+use regex::{Regex, RegexBuilder};
+use std::io::{stdout, IsTerminal};
+
+/// CLI command to search the sessions.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct SearchCommand {
+    /// Regular expression searched in the descriptions, the commands and the output
+    /// recorded with `replay record --with-output`
+    #[arg(value_name = "pattern")]
+    pattern: String,
+
+    /// Ignore case when matching the pattern
+    #[arg(long, short)]
+    ignore_case: bool,
+
+    /// Only search the descriptions and the commands, which is faster than reading the output
+    #[arg(long)]
+    no_output: bool,
+}
+
+impl RunnableCommand for SearchCommand {
+    fn run(&self) -> ReplayResult<()> {
+        let pattern = RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map_err(|e| ReplayError::SessionError(format!("Invalid pattern: {}", e)))?;
+        let matches = Session::search(&pattern, !self.no_output)?;
+        if matches.is_empty() {
+            return Err(ReplayError::SessionError(format!(
+                "No session matches '{}'",
+                self.pattern
+            )));
+        }
+
+        let highlight = stdout().is_terminal();
+        for found in &matches {
+            println!("{}", Self::format_match(found, &pattern, highlight)?);
+        }
+        Ok(())
+    }
+}
+
+impl SearchCommand {
+    fn format_match(found: &SearchMatch, pattern: &Regex, highlight: bool) -> ReplayResult<String> {
+        let names = SessionNameFile::get_names(&found.id)?;
+        let mut lines = vec![format!("replay@{{{}}}", found.index)];
+        if !names.is_empty() {
+            lines[0].push_str(&format!(" ({})", names.join(", ")));
+        }
+        if let Some(desc) = &found.description {
+            let desc = if found.description_matches {
+                Self::highlight(desc, pattern, highlight)
+            } else {
+                desc.clone()
+            };
+            lines[0].push_str(&format!(": {}", desc));
+        }
+        for (i, cmd) in &found.commands {
+            lines.push(format!(
+                "  {}. {}",
+                i + 1,
+                Self::highlight(cmd, pattern, highlight).replace('\n', "\n     ")
+            ));
+        }
+        for (i, line) in &found.output_lines {
+            lines.push(format!(
+                "  {}> {}",
+                i + 1,
+                Self::highlight(line, pattern, highlight)
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn highlight(text: &str, pattern: &Regex, highlight: bool) -> String {
+        if !highlight {
+            return text.to_string();
+        }
+        pattern
+            .replace_all(text, |caps: &regex::Captures| {
+                caps[0].to_string().red().bold().to_string()
+            })
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::setup;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn format_search_match() {
+        setup();
+        let found = SearchMatch {
+            index: 2,
+            id: String::from("unnamed"),
+            description: Some(String::from("deploy to kubernetes")),
+            description_matches: false,
+            commands: vec![(0, String::from("kubectl get pods"))],
+            output_lines: vec![(0, String::from("web-kube-1"))],
+        };
+        let pattern = Regex::new("kube").unwrap();
+        assert_eq!(
+            SearchCommand::format_match(&found, &pattern, false).unwrap(),
+            "replay@{2}: deploy to kubernetes\n  1. kubectl get pods\n  1> web-kube-1"
+        );
+
+        let formatted = SearchCommand::format_match(&found, &pattern, true).unwrap();
+        assert!(formatted.contains(&format!("{}ctl", "kube".red().bold())));
+        // Only the matching parts are highlighted
+        assert!(formatted.contains("deploy to kubernetes"));
+    }
+}
@@ -0,0 +1,270 @@
+//! ShowCommand: Display the metadata and the commands of a session.
+
+use super::RunnableCommand;
+use crate::args;
+use crate::errors::ReplayResult;
+use crate::session::{CommandTiming, Session, SessionNameFile, SessionRef, TerminalSize};
+use crate::shell::Shell;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// CLI command to show a session.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct ShowCommand {
+    /// Session in the form replay@{index}, a session name or an id prefix
+    #[arg(
+        value_name = "session_name",
+        default_value = "replay@{0}",
+        value_parser = args::parse_session_index
+    )]
+    session: SessionRef,
+
+    /// Print the session as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Everything known about a session, as printed by `replay show`
+#[derive(Serialize, Debug)]
+struct SessionDetails {
+    index: u32,
+    id: String,
+    names: Vec<String>,
+    user: String,
+    timestamp: DateTime<Utc>,
+    description: Option<String>,
+    shell: Shell,
+    cwd: Option<PathBuf>,
+    env: BTreeMap<String, String>,
+    size: Option<TerminalSize>,
+    parameters: Vec<String>,
+    command_count: usize,
+    with_output: bool,
+    /// Number of keys recorded with `--raw`, replayed instead of the commands
+    raw_keys: usize,
+    file_size: u64,
+    compressed: bool,
+    commands: Vec<String>,
+    /// Directory each command was run in, if it was reported while recording
+    directories: Vec<Option<PathBuf>>,
+    /// When each command was typed and how long it ran, if it was recorded
+    timings: Vec<Option<CommandTiming>>,
+    /// Whether each command was interrupted with Ctrl+C or Ctrl+\ while recording
+    interrupted: Vec<bool>,
+    /// Whether each command was typed with the history, the completion or keys not followed,
+    /// so that it may differ from what the shell ran
+    inexact: Vec<bool>,
+}
+
+impl RunnableCommand for ShowCommand {
+    fn run(&self) -> ReplayResult<()> {
+        let details = Self::details(self.session.resolve_index()?)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&details)?);
+        } else {
+            print!("{}", details);
+        }
+        Ok(())
+    }
+}
+
+impl ShowCommand {
+    fn details(index: u32) -> ReplayResult<SessionDetails> {
+        let session = Session::load_session_by_index(index)?;
+        let (path, compressed) = Session::get_stored_path(&session.id)?;
+        let commands: Vec<String> = session.iter_script_commands().map(String::from).collect();
+        let directories = (0..commands.len())
+            .map(|i| session.get_command_cwd(i).map(PathBuf::from))
+            .collect();
+        let timings = (0..commands.len())
+            .map(|i| session.get_command_timing(i).cloned())
+            .collect();
+        let interrupted = (0..commands.len())
+            .map(|i| session.is_command_interrupted(i))
+            .collect();
+        let inexact = (0..commands.len())
+            .map(|i| session.is_command_inexact(i))
+            .collect();
+        Ok(SessionDetails {
+            index,
+            names: SessionNameFile::get_names(&session.id)?,
+            parameters: session.parameters.clone(),
+            command_count: commands.len(),
+            with_output: !session.outputs.is_empty(),
+            raw_keys: session.input.iter().map(|chunk| chunk.data.len()).sum(),
+            file_size: std::fs::metadata(path)?.len(),
+            compressed,
+            commands,
+            directories,
+            timings,
+            interrupted,
+            inexact,
+            id: session.id,
+            user: session.user,
+            timestamp: session.timestamp,
+            description: session.description,
+            shell: session.shell,
+            cwd: session.cwd,
+            env: session.env,
+            size: session.size,
+        })
+    }
+}
+
+impl std::fmt::Display for SessionDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let field = |f: &mut std::fmt::Formatter<'_>, name: &str, value: &dyn std::fmt::Display| {
+            writeln!(f, "{:<13}{}", format!("{}:", name), value)
+        };
+        field(f, "Session", &format!("replay@{{{}}}", self.index))?;
+        field(f, "Id", &self.id)?;
+        if !self.names.is_empty() {
+            field(f, "Names", &self.names.join(", "))?;
+        }
+        field(
+            f,
+            "Description",
+            &self.description.as_deref().unwrap_or("-"),
+        )?;
+        field(f, "User", &self.user)?;
+        field(f, "Date", &self.timestamp.to_rfc3339())?;
+        field(f, "Shell", &self.shell)?;
+        if let Some(cwd) = &self.cwd {
+            field(f, "Directory", &cwd.display())?;
+        }
+        if !self.env.is_empty() {
+            let env: Vec<String> = self
+                .env
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            field(f, "Env", &env.join(" "))?;
+        }
+        if let Some(size) = &self.size {
+            field(f, "Size", size)?;
+        }
+        if !self.parameters.is_empty() {
+            field(f, "Parameters", &self.parameters.join(", "))?;
+        }
+        field(
+            f,
+            "Output",
+            &if self.with_output { "recorded" } else { "-" },
+        )?;
+        if self.raw_keys > 0 {
+            field(f, "Keys", &format!("{} recorded with --raw", self.raw_keys))?;
+        }
+        field(
+            f,
+            "File",
+            &format!(
+                "{} bytes{}",
+                self.file_size,
+                if self.compressed { ", compressed" } else { "" }
+            ),
+        )?;
+        field(f, "Commands", &self.command_count)?;
+        let mut current_dir = None;
+        for (i, (cmd, dir)) in self.commands.iter().zip(&self.directories).enumerate() {
+            let mut notes: Vec<String> = self.timings[i]
+                .as_ref()
+                .and_then(|timing| timing.duration_ms)
+                .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                .into_iter()
+                .collect();
+            if self.interrupted[i] {
+                notes.push(String::from("interrupted"));
+            }
+            if self.inexact[i] {
+                notes.push(String::from("may differ"));
+            }
+            let notes = if notes.is_empty() {
+                String::new()
+            } else {
+                format!("  ({})", notes.join(", "))
+            };
+            if let Some(dir) = dir.as_ref().filter(|&dir| Some(dir) != current_dir) {
+                writeln!(f, "  in {}", dir.display())?;
+                current_dir = Some(dir);
+            }
+            let cmd = cmd.replace('\n', "\n     ");
+            writeln!(f, "  {}. {}{}", i + 1, cmd, notes)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::setup;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn show_session_without_exit() {
+        setup();
+        let mut session = Session::new(Some("show session test".into())).unwrap();
+        session.add_command("ls\r".into());
+        session.add_command("echo hello\r".into());
+        session.mark_parameter("word", "hello");
+        session.save_session(false).unwrap();
+
+        let details = ShowCommand::details(0).unwrap();
+        assert_eq!(details.commands, vec!["ls", "echo {{word}}"]);
+        assert_eq!(details.parameters, vec!["word"]);
+        assert!(!details.compressed);
+        assert!(details.file_size > 0);
+
+        let shown = details.to_string();
+        assert!(shown.starts_with("Session:     replay@{0}\n"));
+        assert!(shown.contains("Description: show session test\n"));
+        assert!(shown.ends_with("Commands:    2\n  1. ls\n  2. echo {{word}}\n"));
+    }
+
+    #[test]
+    #[serial]
+    fn show_command_directories() {
+        setup();
+        let mut session = Session::new(None).unwrap();
+        for (cmd, cwd) in [
+            ("cd src\r", "/app"),
+            ("make\r", "/app/src"),
+            ("ls\r", "/app/src"),
+        ] {
+            session.add_command(cmd.into());
+            session.set_command_cwd(cwd.into());
+        }
+        session.mark_command_inexact();
+        session.set_command_timing(CommandTiming {
+            duration_ms: Some(1250),
+            ..Default::default()
+        });
+        session.mark_command_interrupted();
+        session.add_input(0, b'l');
+        session.add_input(40, b's');
+        session.save_session(true).unwrap();
+
+        let shown = ShowCommand::details(0).unwrap().to_string();
+        assert!(shown.contains("Keys:        2 recorded with --raw\n"));
+        assert!(shown.ends_with(
+            "  in /app\n  1. cd src\n  in /app/src\n  2. make\n  3. ls  (1.2s, interrupted, may differ)\n"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn show_empty_session() {
+        setup();
+        let session = Session::new(None).unwrap();
+        session.save_session(true).unwrap();
+
+        let details = ShowCommand::details(0).unwrap();
+        assert!(details.commands.is_empty());
+        assert!(details.compressed);
+        assert!(details.to_string().ends_with("Commands:    0\n"));
+    }
+}
@@ -0,0 +1,67 @@
+//! TagCommand: Give stable names to sessions.
+
+use super::RunnableCommand;
+use crate::args;
+use crate::errors::ReplayResult;
+use crate::session::{SessionIndexFile, SessionNameFile, SessionRef};
+use clap::Args;
+
+/// CLI command to name a session.
+#[derive(Args, PartialEq, Eq, Debug)]
+pub struct TagCommand {
+    /// Session in the form replay@{index}, a session name or an id prefix
+    #[arg(
+        value_name = "session_name",
+        value_parser = args::parse_session_index,
+        required_unless_present = "delete"
+    )]
+    session: Option<SessionRef>,
+
+    /// Name to give to the session
+    #[arg(
+        value_name = "name",
+        value_parser = args::validate_session_name,
+        required_unless_present = "delete"
+    )]
+    name: Option<String>,
+
+    /// Move the name if it is already given to another session
+    #[arg(long, short)]
+    force: bool,
+
+    /// Remove a session name
+    #[arg(long, short, value_name = "name", conflicts_with_all = ["session", "name", "force"])]
+    delete: Option<String>,
+}
+
+impl RunnableCommand for TagCommand {
+    fn run(&self) -> ReplayResult<()> {
+        if let Some(name) = &self.delete {
+            SessionNameFile::remove_name(name)?;
+            println!("Name '{}' removed", name);
+            return Ok(());
+        }
+
+        // Both are required by clap when not deleting
+        let (Some(session), Some(name)) = (&self.session, &self.name) else {
+            unreachable!("Session and name are required without --delete");
+        };
+        let index = session.resolve_index()?;
+        let session_id = SessionIndexFile::get_session_id(index)?;
+        SessionNameFile::set_name(name, &session_id, self.force)?;
+        println!("Session 'replay@{{{}}}' named '{}'", index, name);
+        Ok(())
+    }
+}
+
+impl TagCommand {
+    #[cfg(test)]
+    pub fn new(session: Option<SessionRef>, name: Option<String>, delete: Option<String>) -> Self {
+        Self {
+            session,
+            name,
+            force: false,
+            delete,
+        }
+    }
+}
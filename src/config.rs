@@ -0,0 +1,324 @@
+//! # Config
+//!
+//! Global configuration of `replay`, stored in `~/.replay/config.toml`.
+//! It holds the default value of the command options: an option
+//! given on the command line always wins over the configuration.
+
+use crate::errors::{ReplayError, ReplayResult};
+use crate::paths;
+use crate::session::params;
+use crate::shell::Shell;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml::{Table, Value};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub record: RecordDefaults,
+    pub run: RunDefaults,
+    pub list: ListDefaults,
+    pub pty: PtyDefaults,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordDefaults {
+    pub shell: Shell,
+    pub no_compression: bool,
+    pub compression_level: i32,
+    pub with_output: bool,
+    pub description_min_len: usize,
+    pub description_max_len: usize,
+    /// Environment variables saved with the sessions and set when replaying them
+    pub env: Vec<String>,
+}
+
+impl Default for RecordDefaults {
+    fn default() -> Self {
+        Self {
+            shell: Shell::Bash,
+            no_compression: false,
+            compression_level: 3,
+            with_output: false,
+            description_min_len: 10,
+            description_max_len: 80,
+            env: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunDefaults {
+    /// Delay in milliseconds between each typed character
+    pub delay: u64,
+}
+
+impl Default for RunDefaults {
+    fn default() -> Self {
+        Self { delay: 10 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListDefaults {
+    /// Length after which the session messages are truncated
+    pub truncate: usize,
+}
+
+impl Default for ListDefaults {
+    fn default() -> Self {
+        Self { truncate: 50 }
+    }
+}
+
+/// Size of the PTY when there is no terminal to take it from, e.g. with `run --headless`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PtyDefaults {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for PtyDefaults {
+    fn default() -> Self {
+        Self { rows: 24, cols: 80 }
+    }
+}
+
+/// Configuration loaded once from the config file
+pub fn get() -> ReplayResult<&'static Config> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = Config::load_from(&Config::get_path())?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+impl Config {
+    pub fn get_path() -> PathBuf {
+        paths::replay_dir().join("config.toml")
+    }
+
+    pub fn load_from(path: &Path) -> ReplayResult<Self> {
+        Self::from_table(Self::read_table(path)?)
+    }
+
+    /// Read the values actually written in the config file
+    fn read_table(path: &Path) -> ReplayResult<Table> {
+        if !path.try_exists()? {
+            return Ok(Table::new());
+        }
+        let content = std::fs::read_to_string(path)?;
+        content
+            .parse::<Table>()
+            .map_err(|e| ReplayError::ConfigError(format!("{}: {}", path.display(), e)))
+    }
+
+    fn from_table(table: Table) -> ReplayResult<Self> {
+        let config: Config = Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| ReplayError::ConfigError(e.message().to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> ReplayResult<()> {
+        let invalid = |msg: &str| Err(ReplayError::ConfigError(msg.to_string()));
+        if !(1..=22).contains(&self.record.compression_level) {
+            return invalid("record.compression_level must be between 1 and 22");
+        }
+        if self.record.description_min_len > self.record.description_max_len {
+            return invalid(
+                "record.description_min_len must not exceed record.description_max_len",
+            );
+        }
+        if self.run.delay < 10 {
+            return invalid("run.delay must be at least 10 ms");
+        }
+        if let Some(name) = self
+            .record
+            .env
+            .iter()
+            .find(|name| !params::is_valid_name(name))
+        {
+            return Err(ReplayError::ConfigError(format!(
+                "record.env: invalid environment variable name '{}'",
+                name
+            )));
+        }
+        if self.pty.rows == 0 || self.pty.cols == 0 {
+            return invalid("pty.rows and pty.cols must be positive");
+        }
+        Ok(())
+    }
+
+    /// All the keys and their value, as `section.key`
+    pub fn list(&self) -> Vec<(String, Value)> {
+        let Ok(Value::Table(sections)) = Value::try_from(self) else {
+            unreachable!("Config is always serialized as a table");
+        };
+        let mut entries = Vec::new();
+        for (section, values) in sections {
+            if let Value::Table(values) = values {
+                for (key, value) in values {
+                    entries.push((format!("{}.{}", section, key), value));
+                }
+            }
+        }
+        entries
+    }
+
+    pub fn get_value(&self, key: &str) -> ReplayResult<Value> {
+        self.list()
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+            .ok_or_else(|| ReplayError::ConfigError(format!("Unknown config key '{}'", key)))
+    }
+
+    /// Set `key` in the config file at `path`, the other values written in it are kept as is
+    pub fn set_value(path: &Path, key: &str, value: &str) -> ReplayResult<Self> {
+        let current = Self::default().get_value(key)?;
+        let value = Self::parse_value(&current, value)
+            .ok_or_else(|| ReplayError::ConfigError(format!("Invalid value for '{}'", key)))?;
+
+        let mut table = Self::read_table(path)?;
+        let (section, name) = key.split_once('.').unwrap();
+        let section = table
+            .entry(section)
+            .or_insert_with(|| Value::Table(Table::new()));
+        let Value::Table(section) = section else {
+            return Err(ReplayError::ConfigError(format!(
+                "'{}' is not a section in {}",
+                section,
+                path.display()
+            )));
+        };
+        section.insert(name.to_string(), value);
+
+        // Only save a configuration that can be loaded back
+        let config = Self::from_table(table.clone())?;
+        let content =
+            toml::to_string_pretty(&table).map_err(|e| ReplayError::ConfigError(e.to_string()))?;
+        std::fs::write(path, content)?;
+        Ok(config)
+    }
+
+    /// Parse `value` with the same type as `current`
+    fn parse_value(current: &Value, value: &str) -> Option<Value> {
+        match current {
+            Value::Integer(_) => value.parse::<i64>().ok().map(Value::Integer),
+            Value::Boolean(_) => value.parse::<bool>().ok().map(Value::Boolean),
+            Value::String(_) => Some(Value::String(value.to_string())),
+            // Lists are given comma separated, e.g. `PATH,KUBECONFIG`
+            Value::Array(_) => Some(Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("replay_config_{}.toml", name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn missing_file_gives_defaults() {
+        let path = config_path("missing");
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn partial_file_keeps_other_defaults() {
+        let path = config_path("partial");
+        std::fs::write(&path, "[run]\ndelay = 25\n\n[record]\nshell = \"zsh\"\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.run.delay, 25);
+        assert_eq!(config.record.shell, Shell::Zsh);
+        assert_eq!(config.record.compression_level, 3);
+        assert_eq!(config.pty, PtyDefaults::default());
+    }
+
+    #[test]
+    fn invalid_file() {
+        let path = config_path("invalid");
+        std::fs::write(&path, "[run]\ndelay = \"fast\"\n").unwrap();
+        assert!(matches!(
+            Config::load_from(&path),
+            Err(ReplayError::ConfigError(_))
+        ));
+
+        std::fs::write(&path, "[run]\nspeed = 2\n").unwrap();
+        assert!(matches!(
+            Config::load_from(&path),
+            Err(ReplayError::ConfigError(_))
+        ));
+
+        std::fs::write(&path, "[run]\ndelay = 1\n").unwrap();
+        assert!(matches!(
+            Config::load_from(&path),
+            Err(ReplayError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn get_and_list() {
+        let config = Config::default();
+        assert_eq!(config.get_value("run.delay").unwrap(), Value::Integer(10));
+        assert_eq!(
+            config.get_value("record.shell").unwrap(),
+            Value::String("bash".into())
+        );
+        assert!(config.get_value("run.unknown").is_err());
+        assert!(config
+            .list()
+            .iter()
+            .any(|(key, value)| key == "list.truncate" && *value == Value::Integer(50)));
+    }
+
+    #[test]
+    fn set_only_writes_the_given_key() {
+        let path = config_path("set");
+        let config = Config::set_value(&path, "pty.rows", "40").unwrap();
+        assert_eq!(config.pty.rows, 40);
+        let config = Config::set_value(&path, "record.no_compression", "true").unwrap();
+        assert!(config.record.no_compression);
+        assert_eq!(config.pty.rows, 40);
+        let config = Config::set_value(&path, "record.env", "AWS_PROFILE, KUBECONFIG").unwrap();
+        assert_eq!(config.record.env, vec!["AWS_PROFILE", "KUBECONFIG"]);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("rows = 40"));
+        assert!(!content.contains("cols"));
+        assert_eq!(Config::load_from(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn set_invalid_values() {
+        let path = config_path("set_invalid");
+        assert!(Config::set_value(&path, "run.delay", "fast").is_err());
+        assert!(Config::set_value(&path, "run.delay", "5").is_err());
+        assert!(Config::set_value(&path, "record.shell", "tcsh").is_err());
+        assert!(Config::set_value(&path, "record.env", "AWS-PROFILE").is_err());
+        assert!(Config::set_value(&path, "unknown", "1").is_err());
+        assert!(!path.exists(), "Invalid values should never be written");
+    }
+}
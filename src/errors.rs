@@ -30,6 +30,25 @@ pub enum ReplayError {
     #[error("UTF-8 error: {0}")]
     Utf8(#[from] std::str::Utf8Error),
 
+    #[error("Command `{command}` failed with exit status {status}")]
+    CommandFailed { command: String, status: i32 },
+
+    #[error("Config error: {0}")]
+    ConfigError(String),
+
+    #[error("Shell exited with status {0}")]
+    ShellExit(i32),
+
     #[error("Unknown replay error")]
     Unknown,
 }
+
+impl ReplayError {
+    /// Exit code of `replay` when this error ends the program
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ReplayError::ShellExit(code) => *code,
+            _ => 1,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Export to the asciicast v2 format used by asciinema:
+//! <https://docs.asciinema.org/manual/asciicast/v2/>
+
+use crate::errors::ReplayResult;
+use crate::session::{Session, TerminalSize};
+use serde_json::json;
+use std::io::Write;
+
+/// Delay between two typed characters when the output was not recorded
+const TYPING_DELAY: f64 = 0.05;
+/// Delay after each command when the output was not recorded
+const COMMAND_DELAY: f64 = 0.5;
+
+pub fn write_asciicast<W: Write>(session: &Session, mut writer: W) -> ReplayResult<()> {
+    let size = match session.size {
+        Some(size) => size,
+        None => TerminalSize::from_config()?,
+    };
+    let mut header = json!({
+        "version": 2,
+        "width": size.cols,
+        "height": size.rows,
+        "timestamp": session.timestamp.timestamp(),
+        "env": { "SHELL": session.shell.program() },
+    });
+    if let Some(desc) = &session.description {
+        header["title"] = json!(desc);
+    }
+    writeln!(writer, "{}", header)?;
+
+    for (time, data) in events(session) {
+        writeln!(writer, "{}", json!([time, "o", data]))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Output events as (seconds since start, data)
+fn events(session: &Session) -> Vec<(f64, String)> {
+    if session.outputs.is_empty() {
+        return typed_events(session);
+    }
+
+    let mut events = Vec::new();
+    // A character may be split between two reads of the PTY, its start waits for the rest
+    let mut pending: Vec<u8> = Vec::new();
+    let mut time = 0.0;
+    for chunk in session.outputs.iter().flat_map(|output| &output.chunks) {
+        time = chunk.time_ms as f64 / 1000.0;
+        pending.extend_from_slice(&chunk.data);
+        let complete = complete_utf8_len(&pending);
+        if complete > 0 {
+            let data: Vec<u8> = pending.drain(..complete).collect();
+            events.push((time, String::from_utf8_lossy(&data).to_string()));
+        }
+    }
+    if !pending.is_empty() {
+        events.push((time, String::from_utf8_lossy(&pending).to_string()));
+    }
+    events
+}
+
+/// Length of `bytes` without the start of a character cut at the end.
+/// Invalid bytes before it are kept, they are replaced when converted.
+fn complete_utf8_len(bytes: &[u8]) -> usize {
+    let mut start = 0;
+    loop {
+        match std::str::from_utf8(&bytes[start..]) {
+            Ok(_) => return bytes.len(),
+            Err(err) => match err.error_len() {
+                Some(len) => start += err.valid_up_to() + len,
+                None => return start + err.valid_up_to(),
+            },
+        }
+    }
+}
+
+/// Without recorded output, we simulate the commands being typed after a prompt,
+/// at the time they were typed if it was recorded
+fn typed_events(session: &Session) -> Vec<(f64, String)> {
+    let mut events = Vec::new();
+    let mut time = 0.0;
+    for (i, cmd) in session.iter_commands().enumerate() {
+        let chars: Vec<char> = cmd.trim_end_matches('\r').chars().collect();
+        let (typing_delay, command_delay) = match session.get_command_timing(i) {
+            Some(timing) => {
+                // Times never go backwards, even if the timing of a command is missing
+                time = f64::max(time, timing.prompt_ms as f64 / 1000.0);
+                events.push((time, "$ ".to_string()));
+                time += timing.idle_ms() as f64 / 1000.0;
+                (
+                    timing.typing_ms() as f64 / 1000.0 / (chars.len() + 1) as f64,
+                    timing.duration_ms.unwrap_or_default() as f64 / 1000.0,
+                )
+            }
+            None => {
+                events.push((time, "$ ".to_string()));
+                (TYPING_DELAY, COMMAND_DELAY)
+            }
+        };
+        for c in chars {
+            time += typing_delay;
+            // The lines of a multi-line command follow a continuation prompt
+            let typed = if c == '\n' {
+                "\r\n> ".to_string()
+            } else {
+                c.to_string()
+            };
+            events.push((time, typed));
+        }
+        time += typing_delay;
+        events.push((time, "\r\n".to_string()));
+        time += command_delay;
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{CommandTiming, OutputChunk};
+
+    fn export(session: &Session) -> Vec<serde_json::Value> {
+        let mut buf = Vec::new();
+        write_asciicast(session, &mut buf).unwrap();
+        String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn header_uses_session_metadata() {
+        let session = Session::new(Some("asciicast header".into())).unwrap();
+        let lines = export(&session);
+        let header = &lines[0];
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 80);
+        assert_eq!(header["height"], 24);
+        assert_eq!(header["title"], "asciicast header");
+        assert_eq!(header["timestamp"], session.timestamp.timestamp());
+    }
+
+    #[test]
+    fn header_uses_recorded_size() {
+        let mut session = Session::new(None).unwrap();
+        session.size = Some(TerminalSize {
+            rows: 50,
+            cols: 200,
+        });
+        let header = &export(&session)[0];
+        assert_eq!(header["width"], 200);
+        assert_eq!(header["height"], 50);
+    }
+
+    #[test]
+    fn events_from_recorded_output() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command("echo hi\r".into());
+        session.add_command_output(vec![
+            OutputChunk {
+                time_ms: 1500,
+                data: b"hi\r\n".to_vec(),
+            },
+            OutputChunk {
+                time_ms: 1750,
+                data: b"$ ".to_vec(),
+            },
+        ]);
+        let lines = export(&session);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], json!([1.5, "o", "hi\r\n"]));
+        assert_eq!(lines[2], json!([1.75, "o", "$ "]));
+    }
+
+    #[test]
+    fn events_with_characters_split_between_chunks() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command("echo é\r".into());
+        let bytes = "é€".as_bytes();
+        session.add_command_output(vec![
+            OutputChunk {
+                time_ms: 100,
+                data: [b"a", &bytes[..1]].concat(),
+            },
+            OutputChunk {
+                time_ms: 200,
+                data: [&bytes[1..2], b"\xff", &bytes[2..3]].concat(),
+            },
+            OutputChunk {
+                time_ms: 300,
+                data: bytes[3..].to_vec(),
+            },
+        ]);
+        let lines = export(&session);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], json!([0.1, "o", "a"]));
+        assert_eq!(lines[2], json!([0.2, "o", "é\u{fffd}"]));
+        assert_eq!(lines[3], json!([0.3, "o", "€"]));
+    }
+
+    #[test]
+    fn events_without_recorded_output() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command("ls\r".into());
+        session.add_command("echo 'a\nb'\r".into());
+        session.add_command("exit\r".into());
+        let events = typed_events(&session);
+        let data: String = events.iter().map(|(_, d)| d.as_str()).collect();
+        assert_eq!(data, "$ ls\r\n$ echo 'a\r\n> b'\r\n$ exit\r\n");
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn events_with_recorded_timing() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command("ls\r".into());
+        session.set_command_timing(CommandTiming {
+            prompt_ms: 500,
+            typed_ms: 2500,
+            entered_ms: 3100,
+            duration_ms: Some(400),
+            ..Default::default()
+        });
+        session.add_command("exit\r".into());
+        let events = typed_events(&session);
+        let times: Vec<f64> = events.iter().map(|(t, _)| (t * 1000.0).round()).collect();
+        // The prompt, then `l`, `s` and Enter typed in 600 ms after 2 s, then the next prompt
+        assert_eq!(times[..5], [500.0, 2700.0, 2900.0, 3100.0, 3500.0]);
+        assert_eq!(events[4].1, "$ ");
+    }
+}
@@ -0,0 +1,37 @@
+//! # Export
+//!
+//! `Export` converts a recorded session into a format
+//! that can be used outside of `replay`.
+
+use crate::errors::ReplayResult;
+use crate::session::Session;
+use clap::ValueEnum;
+use std::io::Write;
+
+mod asciicast;
+mod sh;
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    /// asciinema cast file (asciicast v2)
+    Asciicast,
+    /// Standalone script for the shell the session was recorded in
+    Sh,
+}
+
+#[derive(Default)]
+pub struct ExportConfig {
+    pub strict: bool, // add `set -euo pipefail` (`set -eu` in sh) to scripts
+}
+
+pub fn export_session<W: Write>(
+    session: &Session,
+    format: ExportFormat,
+    config: &ExportConfig,
+    writer: W,
+) -> ReplayResult<()> {
+    match format {
+        ExportFormat::Asciicast => asciicast::write_asciicast(session, writer),
+        ExportFormat::Sh => sh::write_script(session, config, writer),
+    }
+}
@@ -0,0 +1,163 @@
+//! Export to a standalone script, for the shell the session was recorded in.
+
+use super::ExportConfig;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::session::Session;
+use crate::shell::Shell;
+use std::io::Write;
+
+pub fn write_script<W: Write>(
+    session: &Session,
+    config: &ExportConfig,
+    mut writer: W,
+) -> ReplayResult<()> {
+    let shell = session.shell;
+    // fish has no equivalent of `set -e`
+    let strict_mode = match shell {
+        Shell::Bash | Shell::Zsh => "set -euo pipefail",
+        Shell::Sh => "set -eu",
+        Shell::Fish if config.strict => {
+            return Err(ReplayError::SessionError(String::from(
+                "A fish script can't stop on the first error, export it without --strict",
+            )))
+        }
+        Shell::Fish => "",
+    };
+    writeln!(writer, "{}", shebang(shell))?;
+    if let Some(desc) = &session.description {
+        writeln!(writer, "# {}", desc)?;
+    }
+    writeln!(
+        writer,
+        "# Recorded by {} on {}",
+        session.user,
+        session.timestamp.to_rfc3339()
+    )?;
+    if config.strict {
+        writeln!(writer, "{}", strict_mode)?;
+    }
+    writeln!(writer)?;
+
+    // Explicit `cd` lines, so the script runs in the recorded directories wherever it is started
+    let mut current_dir = None;
+    for (i, cmd) in session.iter_script_commands().enumerate() {
+        let dir = session
+            .get_command_cwd(i)
+            .or_else(|| session.cwd.as_deref().filter(|_| i == 0));
+        if let Some(dir) = dir.filter(|&dir| Some(dir) != current_dir) {
+            writeln!(writer, "cd {}", quote(&dir.to_string_lossy(), shell))?;
+            current_dir = Some(dir);
+        }
+        writeln!(writer, "{}", cmd)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn shebang(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => "#!/usr/bin/env bash",
+        Shell::Zsh => "#!/usr/bin/env zsh",
+        Shell::Sh => "#!/bin/sh",
+        Shell::Fish => "#!/usr/bin/env fish",
+    }
+}
+
+/// Quote `s` as a single shell word
+fn quote(s: &str, shell: Shell) -> String {
+    match shell {
+        // fish single quotes allow `\'` and `\\` escapes
+        Shell::Fish => format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'")),
+        _ => format!("'{}'", s.replace('\'', r"'\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(session: &Session, strict: bool) -> String {
+        let mut buf = Vec::new();
+        write_script(session, &ExportConfig { strict }, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn script_from_session() {
+        let mut session = Session::new(Some("deploy to staging".into())).unwrap();
+        session.add_command("cd /tmp\r".into());
+        session.add_command("ls -la\r".into());
+        session.add_command("exit\r".into());
+
+        let expected = format!(
+            "#!/usr/bin/env bash\n# deploy to staging\n# Recorded by {} on {}\n\ncd /tmp\nls -la\n",
+            session.user,
+            session.timestamp.to_rfc3339()
+        );
+        assert_eq!(export(&session, false), expected);
+    }
+
+    #[test]
+    fn script_with_directories() {
+        let mut session = Session::new(None).unwrap();
+        session.cwd = Some("/home/me".into());
+        for (cmd, cwd) in [
+            ("ls\r", "/home/me"),
+            ("cd it's\r", "/home/me"),
+            ("make\r", "/home/me/it's"),
+            ("make test\r", "/home/me/it's"),
+            ("exit\r", "/home/me/it's"),
+        ] {
+            session.add_command(cmd.into());
+            session.set_command_cwd(cwd.into());
+        }
+
+        let script = export(&session, false);
+        let commands: Vec<&str> = script.lines().skip(3).collect();
+        assert_eq!(
+            commands,
+            vec![
+                "cd '/home/me'",
+                "ls",
+                "cd it's",
+                r"cd '/home/me/it'\''s'",
+                "make",
+                "make test"
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_script() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command("false\r".into());
+
+        let script = export(&session, true);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(lines[0], "#!/usr/bin/env bash");
+        assert!(lines[1].starts_with("# Recorded by "));
+        assert_eq!(lines[2], "set -euo pipefail");
+        assert_eq!(lines[4], "false");
+    }
+
+    #[test]
+    fn script_for_the_recorded_shell() {
+        let mut session = Session::new(None).unwrap();
+        session.cwd = Some("/home/it's".into());
+        session.add_command("ls\r".into());
+
+        session.shell = Shell::Sh;
+        let script = export(&session, true);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(lines[0], "#!/bin/sh");
+        assert_eq!(lines[2], "set -eu");
+
+        session.shell = Shell::Fish;
+        let script = export(&session, false);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(lines[0], "#!/usr/bin/env fish");
+        assert_eq!(lines[3], r"cd '/home/it\'s'");
+        let mut buf = Vec::new();
+        assert!(write_script(&session, &ExportConfig { strict: true }, &mut buf).is_err());
+    }
+}
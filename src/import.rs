@@ -0,0 +1,160 @@
+//! # Import
+//!
+//! `Import` builds the commands of a session from existing
+//! sources instead of recording them live in the PTY.
+
+use regex::Regex;
+
+/// Parse a shell script: one command per line, skipping the shebang, the blank lines
+/// and the comments. The lines are kept as they are written, and the body of a heredoc
+/// belongs to the command opening it, as a multi-line command.
+pub fn parse_script(script: &str) -> Vec<String> {
+    // `<<EOF`, `<<-EOF`, `<< 'EOF'`... but not the `<<<` here-strings
+    let re_heredoc = Regex::new(r#"(?:^|[^<])<<(-?)\s*['"]?(\w+)['"]?"#).unwrap();
+    // `$(( ))` and `(( ))`, where `<<` is a shift, with one level of nested parentheses
+    let re_arithmetic = Regex::new(r"\(\((?:[^()]|\([^()]*\))*\)\)").unwrap();
+    let mut commands: Vec<String> = Vec::new();
+    // Line ending the heredoc being read, and whether it may be indented with tabs (`<<-`)
+    let mut heredoc: Option<(String, bool)> = None;
+    for line in script.lines() {
+        if let Some((delimiter, strip_tabs)) = &heredoc {
+            let end = if *strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            if end == delimiter {
+                heredoc = None;
+            }
+            if let Some(cmd) = commands.last_mut() {
+                cmd.push('\n');
+                cmd.push_str(line);
+            }
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let code = re_arithmetic.replace_all(line, "");
+        // A shift by a number outside of `(( ))` is not a heredoc either
+        if let Some(caps) = re_heredoc
+            .captures_iter(&code)
+            .find(|caps| !caps[2].bytes().all(|b| b.is_ascii_digit()))
+        {
+            heredoc = Some((caps[2].to_string(), !caps[1].is_empty()));
+        }
+        commands.push(line.to_string());
+    }
+    commands
+}
+
+/// Parse a bash or zsh history file.
+/// Bash timestamps (`#1700000000`) are skipped and the zsh
+/// extended history prefix (`: 1700000000:0;`) is removed.
+pub fn parse_history(history: &str) -> Vec<String> {
+    let re_bash_timestamp = Regex::new(r"^#\d+$").unwrap();
+    let re_zsh_prefix = Regex::new(r"^: \d+:\d+;").unwrap();
+
+    history
+        .lines()
+        .filter(|line| !re_bash_timestamp.is_match(line))
+        .map(|line| re_zsh_prefix.replace(line, "").trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Keep the entries between `from` and `to`, both 1-based and inclusive
+pub fn select_range(commands: Vec<String>, from: Option<usize>, to: Option<usize>) -> Vec<String> {
+    let from = from.unwrap_or(1);
+    let to = to.unwrap_or(commands.len());
+    commands
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| (from..=to).contains(&(i + 1)))
+        .map(|(_, cmd)| cmd)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_skips_comments_and_blank_lines() {
+        let script = "#!/usr/bin/env bash\n# setup\n\ncd /tmp\n  ls -la  \n";
+        assert_eq!(parse_script(script), vec!["cd /tmp", "  ls -la  "]);
+    }
+
+    #[test]
+    fn script_keeps_heredoc_bodies() {
+        let script = "cat <<EOF > conf\n  indented\n# not a comment\n\nEOF\n\
+                      cat <<-'END'\n\tbody\n\tEND\ncat <<< word\necho done\n";
+        assert_eq!(
+            parse_script(script),
+            vec![
+                "cat <<EOF > conf\n  indented\n# not a comment\n\nEOF",
+                "cat <<-'END'\n\tbody\n\tEND",
+                "cat <<< word",
+                "echo done",
+            ]
+        );
+    }
+
+    #[test]
+    fn script_shifts_are_not_heredocs() {
+        let script = "echo $((1 << 4))\n(( x = y<<n ))\nlet z=1<<2\necho done\n";
+        assert_eq!(
+            parse_script(script),
+            vec![
+                "echo $((1 << 4))",
+                "(( x = y<<n ))",
+                "let z=1<<2",
+                "echo done"
+            ]
+        );
+        // A heredoc after an arithmetic expansion is still read
+        let script = "cat <<EOF $((2 << 1))\nbody\nEOF\necho done\n";
+        assert_eq!(
+            parse_script(script),
+            vec!["cat <<EOF $((2 << 1))\nbody\nEOF", "echo done"]
+        );
+    }
+
+    #[test]
+    fn bash_history_with_timestamps() {
+        let history = "#1700000000\nls\n#1700000001\ngit status\n";
+        assert_eq!(parse_history(history), vec!["ls", "git status"]);
+    }
+
+    #[test]
+    fn zsh_extended_history() {
+        let history = ": 1700000000:0;ls\n: 1700000001:12;make test\n";
+        assert_eq!(parse_history(history), vec!["ls", "make test"]);
+    }
+
+    #[test]
+    fn history_keeps_comments_in_commands() {
+        // Unlike scripts, `#` lines that are not timestamps are commands
+        let history = "echo a # comment\n#not a timestamp\n";
+        assert_eq!(
+            parse_history(history),
+            vec!["echo a # comment", "#not a timestamp"]
+        );
+    }
+
+    #[test]
+    fn range_selection() {
+        let commands: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        assert_eq!(
+            select_range(commands.clone(), Some(2), Some(3)),
+            vec!["b", "c"]
+        );
+        assert_eq!(
+            select_range(commands.clone(), Some(3), None),
+            vec!["c", "d"]
+        );
+        assert_eq!(select_range(commands.clone(), None, Some(1)), vec!["a"]);
+        assert!(select_range(commands, Some(5), None).is_empty());
+    }
+}
@@ -10,15 +10,24 @@
 //!
 //! - [`args`] Defines the command-line interface using `clap`.
 //! - [`commands`] Contains implementations of all supported subcommands.
+//! - [`config`] Loads the default options from the configuration file.
 //! - [`errors`] Defines custom error types for the library.
+//! - [`export`] Converts recorded sessions to other formats.
+//! - [`import`] Builds sessions from shell scripts and histories.
 
 pub mod args;
 pub mod char_buffer;
 pub mod commands;
+pub mod config;
 pub mod errors;
+pub mod export;
+pub mod import;
 pub mod paths;
 pub mod pty;
 pub mod session;
+pub mod shell;
+pub mod signals;
+pub mod step_reader;
 
 use errors::ReplayResult;
 
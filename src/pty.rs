@@ -1,147 +1,376 @@
+use crate::args;
 use crate::char_buffer::CharBuffer;
 use crate::errors::{ReplayError, ReplayResult};
-use crate::session::Session;
+use crate::session::{CommandTiming, OutputChunk, Session, SessionNameFile, TerminalSize};
+use crate::shell::Shell;
+use crate::signals::{RawMode, SignalHandler};
 use crossterm::terminal;
-use portable_pty::{Child, CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use portable_pty::{Child, MasterPty, NativePtySystem, PtySize, PtySystem};
+use regex::bytes::Regex as BytesRegex;
 use regex::Regex;
-use std::io::{BufRead, BufReader, Read, Write};
-use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 type Reader = Box<dyn Read + Send>;
 type Writer = Box<dyn Write + Send>;
 type ChildProc = Box<dyn Child + Send + Sync>;
+type Master = Box<dyn MasterPty + Send>;
 
 #[derive(Default)]
 pub struct RecordConfig {
     pub record_input: bool,                  // enable recording of typed commands
     pub session_description: Option<String>, // optional session description
     pub no_compression: bool,                // disable compression
+    pub edit_description: bool,              // prompt for the description at the end
+    pub record_output: bool,                 // enable recording of the shell output
+    pub session_name: Option<String>,        // optional session name
+    pub fail_fast: bool,                     // stop at the first failing command
+    pub headless: bool,                      // don't use the terminal, for non-interactive runs
+    pub shell: Shell,                        // shell spawned in the PTY
+    pub parameters: Vec<(String, String)>,   // values to save as `{{name}}` placeholders
+    pub cwd: Option<PathBuf>, // directory the shell starts in, the home directory if None
+    pub env: BTreeMap<String, String>, // environment variables set in the shell
+    pub size: Option<TerminalSize>, // PTY size without terminal, the config one if None
+    pub raw: bool,            // send the keys as typed and record them, for interactive programs
+}
+
+/// What happened in the shell once the input is over
+#[derive(Debug)]
+pub struct RunOutcome {
+    pub statuses: Vec<CommandStatus>,
+    pub exit_code: u32,
+}
+
+/// Exit status of a command sent to the shell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandStatus {
+    pub command: String,
+    pub status: Option<i32>, // None if the shell did not report it
+    pub interrupted: bool,   // Ctrl+C or Ctrl+\ was forwarded to the command
+}
+
+impl CommandStatus {
+    pub fn failed(&self) -> bool {
+        self.status.is_some_and(|status| status != 0)
+    }
+}
+
+/// What the shell reports before displaying a prompt
+#[derive(Default, Debug, PartialEq, Eq)]
+struct PromptInfo {
+    ready: bool,          // a prompt is displayed, the shell waits for input
+    status: Option<i32>,  // exit status of the last command
+    cwd: Option<PathBuf>, // current directory of the shell
+    continuation: bool,   // the prompt is PS2, the command is not complete
+}
+
+/// Extract the markers printed by `__replay_status` from the PTY output:
+/// the exit status and the current directory as an OSC 7 sequence,
+/// or `ps2` when the shell displays its continuation prompt.
+/// The markers are removed from the output shown to the user.
+struct PromptInfoParser {
+    re_marker: BytesRegex,
+    pending: Vec<u8>,
+}
+
+impl PromptInfoParser {
+    /// Longest incomplete sequence kept between two chunks, enough for a path
+    const MAX_PENDING: usize = 4096;
+
+    fn new() -> Self {
+        Self {
+            re_marker: BytesRegex::new(r"\x1b\](6973|7);([^\x07\x1b]*)\x07").unwrap(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Update `info` with the last markers found, they may be split between two chunks.
+    /// Return the output without the markers, an incomplete one is kept for the next chunk.
+    fn parse(&mut self, chunk: &[u8], info: &mut PromptInfo) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+        let mut output = Vec::with_capacity(self.pending.len());
+        let mut end = 0;
+        for caps in self.re_marker.captures_iter(&self.pending) {
+            let marker = caps.get(0).unwrap();
+            output.extend_from_slice(&self.pending[end..marker.start()]);
+            end = marker.end();
+            let value = String::from_utf8_lossy(&caps[2]);
+            match &caps[1] {
+                b"6973" if value == "ps2" => {
+                    info.ready = true;
+                    info.continuation = true;
+                }
+                b"6973" => {
+                    info.ready = true;
+                    info.status = value.parse().ok();
+                }
+                _ => info.cwd = Self::parse_file_url(&value).or(info.cwd.take()),
+            }
+        }
+
+        let rest = &self.pending[end..];
+        let keep_from = match rest.iter().rposition(|&b| b == b'\x1b') {
+            Some(i) if rest.len() - i <= Self::MAX_PENDING && Self::is_marker_start(&rest[i..]) => {
+                i
+            }
+            _ => rest.len(),
+        };
+        output.extend_from_slice(&rest[..keep_from]);
+        self.pending.drain(..end + keep_from);
+        output
+    }
+
+    /// Whether `seq`, starting with `ESC`, may be the beginning of a marker
+    fn is_marker_start(seq: &[u8]) -> bool {
+        let Some(rest) = seq.strip_prefix(b"\x1b]") else {
+            return seq == b"\x1b";
+        };
+        [&b"6973;"[..], b"7;"].iter().any(|code| {
+            if rest.len() <= code.len() {
+                code.starts_with(rest)
+            } else {
+                rest.starts_with(code) && !rest.contains(&b'\x07')
+            }
+        })
+    }
+
+    /// Path of a `file://host/path` URL, where the path may be percent-encoded
+    fn parse_file_url(url: &str) -> Option<PathBuf> {
+        let rest = url.strip_prefix("file://")?;
+        let path = &rest[rest.find('/')?..];
+        let mut bytes = Vec::with_capacity(path.len());
+        let mut i = 0;
+        while i < path.len() {
+            let decoded = path
+                .get(i + 1..i + 3)
+                .filter(|_| path.as_bytes()[i] == b'%')
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match decoded {
+                Some(byte) => {
+                    bytes.push(byte);
+                    i += 3;
+                }
+                None => {
+                    bytes.push(path.as_bytes()[i]);
+                    i += 1;
+                }
+            }
+        }
+        Some(PathBuf::from(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+}
+
+/// Output read from the PTY, waiting to be attached to the current command
+struct OutputCapture {
+    start: Instant,
+    chunks: Mutex<Vec<OutputChunk>>,
+}
+
+impl OutputCapture {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            chunks: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn push(&self, data: &[u8]) {
+        let chunk = OutputChunk {
+            time_ms: self.start.elapsed().as_millis() as u64,
+            data: data.to_vec(),
+        };
+        self.chunks.lock().unwrap().push(chunk);
+    }
+
+    /// Return the captured chunks and start a new capture
+    fn take(&self) -> Vec<OutputChunk> {
+        std::mem::take(&mut *self.chunks.lock().unwrap())
+    }
 }
 
 pub fn run_internal<R: Read, W: Write + Send + 'static>(
-    user_input: R,               // input from user (stdin, pipe…)
-    user_output: W,              // output to user (stdout, file…)
-    record_config: RecordConfig, // input config (recording, description, compression)
-) -> ReplayResult<()> {
-    terminal::enable_raw_mode()?;
-    let (ps1_received_sender, ps1_received_receiver) = mpsc::sync_channel::<()>(1);
+    mut user_input: R,               // input from user (stdin, pipe…)
+    user_output: W,                  // output to user (stdout, file…)
+    mut record_config: RecordConfig, // input config (recording, description, compression)
+) -> ReplayResult<RunOutcome> {
+    record_config.size = Some(initial_size(&record_config)?);
+    let raw_mode = if record_config.headless {
+        None
+    } else {
+        Some(RawMode::enable()?)
+    };
+    let (prompt_sender, prompt_receiver) = mpsc::sync_channel::<PromptInfo>(1);
     let (command_sent_sender, command_sent_receiver) = mpsc::sync_channel::<()>(1);
-    let (mut pty_stdout, mut pty_stdin, child) = spawn_shell()?;
-    let ps1 = get_last_ps1_char(&mut pty_stdin, &mut pty_stdout)?;
+    let (pty_stdout, pty_stdin, mut child, master) = spawn_shell(&record_config)?;
+    // Without terminal, nothing can be resized
+    let signal_handler = SignalHandler::start(master, child.process_id(), !record_config.headless)?;
+    let output_capture = (record_config.record_input && record_config.record_output)
+        .then(|| Arc::new(OutputCapture::new()));
 
     // Thread to read from the PTY and send data by user_output.
+    let reader_capture = output_capture.clone();
     let output_reader = thread::spawn(move || {
         read_from_pty(
             pty_stdout,
             user_output,
-            ps1_received_sender,
+            prompt_sender,
             command_sent_receiver,
-            ps1,
+            reader_capture,
         )
     });
 
-    let exit_msg = handle_user_input(
-        user_input,
-        pty_stdin,
-        child,
-        ps1_received_receiver,
-        command_sent_sender,
-        record_config,
-    )?;
-    terminal::disable_raw_mode()?;
+    let handshake = PromptHandshake {
+        prompt: prompt_receiver,
+        command_sent: command_sent_sender,
+    };
+    let (session, statuses) = if record_config.raw {
+        handle_raw_input(
+            &mut user_input,
+            pty_stdin,
+            &mut child,
+            handshake,
+            &record_config,
+            output_capture,
+        )?
+    } else {
+        handle_user_input(
+            &mut user_input,
+            pty_stdin,
+            &mut child,
+            handshake,
+            &record_config,
+            output_capture,
+            &signal_handler,
+        )?
+    };
+    wait_shell_exit(&mut child)?;
+    drop(raw_mode);
+    drop(signal_handler);
     join_output_thread(output_reader)?;
+    let exit_status = child.wait()?;
 
-    if let Some(msg) = exit_msg {
+    if let Some(msg) = save_recorded_session(session, &mut user_input, &record_config)? {
         println!("{}", msg);
     }
 
-    Ok(())
+    Ok(RunOutcome {
+        statuses,
+        exit_code: exit_status.exit_code(),
+    })
 }
 
-fn spawn_shell() -> ReplayResult<(Reader, Writer, ChildProc)> {
-    let pty_system = NativePtySystem::default();
+/// Size of the terminal, or the one of `record_config` without terminal
+fn initial_size(record_config: &RecordConfig) -> ReplayResult<TerminalSize> {
+    if !record_config.headless {
+        match terminal::size() {
+            Ok((cols, rows)) if cols > 0 && rows > 0 => return Ok(TerminalSize { rows, cols }),
+            _ => {}
+        }
+    }
+    match record_config.size {
+        Some(size) => Ok(size),
+        None => TerminalSize::from_config(),
+    }
+}
 
-    // Open a pseudo-terminal
-    let pty_pair = pty_system.openpty(PtySize {
-        rows: 24,
-        cols: 80,
+pub(crate) fn pty_size(size: TerminalSize) -> PtySize {
+    PtySize {
+        rows: size.rows,
+        cols: size.cols,
         pixel_width: 0,
         pixel_height: 0,
-    })?;
+    }
+}
 
-    // Spawn bash inside PTY
-    let bash_cmd = CommandBuilder::new("/bin/bash");
-    let bash_process = pty_pair.slave.spawn_command(bash_cmd)?;
+fn spawn_shell(record_config: &RecordConfig) -> ReplayResult<(Reader, Writer, ChildProc, Master)> {
+    let pty_system = NativePtySystem::default();
+    let size = match record_config.size {
+        Some(size) => size,
+        None => TerminalSize::from_config()?,
+    };
+
+    // Open a pseudo-terminal
+    let pty_pair = pty_system.openpty(pty_size(size))?;
+
+    // Spawn the shell inside PTY
+    let mut command = record_config.shell.command()?;
+    if let Some(cwd) = &record_config.cwd {
+        command.cwd(cwd);
+    }
+    for (key, value) in &record_config.env {
+        command.env(key, value);
+    }
+    let bash_process = pty_pair.slave.spawn_command(command)?;
     drop(pty_pair.slave); // not needed anymore
 
     // PTY handles for I/O
     let pty_stdout = pty_pair.master.try_clone_reader()?; // bash → user
     let pty_stdin = pty_pair.master.take_writer()?; // user → bash
-    Ok((pty_stdout, pty_stdin, bash_process))
+    Ok((pty_stdout, pty_stdin, bash_process, pty_pair.master))
 }
-fn is_env_var_output(line: &str) -> bool {
-    let line = line.trim();
-    let re_ansi = regex::Regex::new(r"\x1b\[[0-9;?]*[a-zA-Z]").unwrap();
-    let line = re_ansi.replace_all(line, "");
 
-    line.starts_with("$(") && line.ends_with(")")
+/// Main thread side of the synchronization with `read_from_pty`
+struct PromptHandshake {
+    prompt: Receiver<PromptInfo>, // a prompt is displayed
+    command_sent: SyncSender<()>, // a command was sent, the next prompt is expected
 }
 
-pub fn get_last_ps1_char(pty_stdin: &mut Writer, pty_stdout: &mut Reader) -> ReplayResult<char> {
-    let mut last_output = String::from("$PS1");
-    let mut reader: BufReader<&mut Box<dyn Read + Send>> = BufReader::new(pty_stdout);
-    let re_non_printable = regex::Regex::new(r"\x1b\[[0-9;?]*[a-zA-Z]|\x01|\x02").unwrap();
-
-    loop {
-        pty_stdin.write_all(format!("echo \"{}\" \r", last_output).as_bytes())?;
-        pty_stdin.flush()?;
-        let mut line = String::new();
-        loop {
-            reader.read_line(&mut line)?;
-            let line_cleaned = re_non_printable.replace_all(&line, "");
-            let line_trimmed = line_cleaned.trim();
-            if !line_trimmed.is_empty() && !line_trimmed.contains("echo") {
-                if !is_env_var_output(line_trimmed) {
-                    let last_char = line_trimmed.chars().next_back().unwrap();
-                    return Ok(last_char);
-                }
-
-                last_output = line_trimmed.to_string();
-                break;
-            }
-            line.clear();
-        }
+/// Session to record the commands in, if `record_input` is set
+fn new_session(record_config: &RecordConfig) -> ReplayResult<Option<Session>> {
+    if !record_config.record_input {
+        return Ok(None);
     }
+    let mut session = Session::new(record_config.session_description.clone())?;
+    session.shell = record_config.shell;
+    session.cwd = record_config.cwd.clone();
+    session.env = record_config.env.clone();
+    session.size = record_config.size;
+    Ok(Some(session))
 }
 
 // Precondition: Terminal is in raw mode
 fn handle_user_input<R: Read, W: Write>(
     mut user_input: R,
     mut pty_stdin: W,
-    mut child: ChildProc,
-    bash_ready_receiver: Receiver<()>,
-    command_sent_sender: SyncSender<()>,
-    record_config: RecordConfig,
-) -> ReplayResult<Option<String>> {
+    child: &mut ChildProc,
+    handshake: PromptHandshake,
+    record_config: &RecordConfig,
+    output_capture: Option<Arc<OutputCapture>>,
+    signal_handler: &SignalHandler,
+) -> ReplayResult<(Option<Session>, Vec<CommandStatus>)> {
     // Main thread sends user input to bash stdin
     let mut buf = [0u8; 1]; // We only read one byte in raw mode
     let mut char_buffer = CharBuffer::new();
     let exit_re = Regex::new(r"^\s*exit\s*$").unwrap();
     let mut first_init = true;
-    let mut session: Option<Session> = if record_config.record_input {
-        Some(Session::new(record_config.session_description)?)
-    } else {
-        None
-    };
+    let mut statuses: Vec<CommandStatus> = Vec::new();
+    let mut sent_command = String::new();
+    // Directory of the shell, as reported before each prompt
+    let mut shell_cwd: Option<PathBuf> = None;
+    // Timing of the command being typed, in ms since `start`
+    let start = Instant::now();
+    let elapsed_ms = || start.elapsed().as_millis() as u64;
+    let mut timing = CommandTiming::default();
+    let mut typing = false;
+    // The shell displayed a continuation prompt, the next line belongs to the same command
+    let mut continuing = false;
+    let mut session = new_session(record_config)?;
+    // Nested shells, ssh or sudo read the terminal while their command runs
+    // SAFETY: `isatty` has no memory safety requirement
+    let forward_keys = !record_config.headless && unsafe { libc::isatty(libc::STDIN_FILENO) } == 1;
     loop {
         if child.try_wait()?.is_some() {
             // Check if the child process has exited
             break;
         }
         if first_init {
-            bash_ready_receiver.recv().unwrap();
+            shell_cwd = handshake.prompt.recv().unwrap().cwd;
+            timing.prompt_ms = elapsed_ms();
             first_init = false;
         }
         let n = user_input.read(&mut buf)?;
@@ -150,69 +379,392 @@ fn handle_user_input<R: Read, W: Write>(
         } else if n != 1 {
             unreachable!("Unexpected read size, should be 1 in terminal raw mode!");
         }
+        if !typing {
+            timing.typed_ms = elapsed_ms();
+            timing.keys_ms.clear();
+            typing = true;
+        }
+        timing.keys_ms.push(elapsed_ms() - timing.typed_ms);
 
         let c = buf[0];
+        // A pasted line end is inserted in the line, the shell runs it on Enter
+        let line_end = matches!(c, b'\r' | b'\n') && !char_buffer.is_pasting();
 
         // Handle input locally
         match c {
-            // Backspace
-            b'\x7F' => {
-                char_buffer.pop_char();
-            }
-            // Ctrl+W
-            b'\x17' => {
-                char_buffer.pop_word();
-            }
-            // Ctrl+C
+            // Ctrl+C at the prompt cancels the line, interrupted commands are handled below
             b'\x03' => {
-                if let Some(sess) = session.as_mut() {
-                    sess.remove_last_command();
+                if continuing {
+                    // The beginning of the multi-line command did not run
+                    if let Some(sess) = session.as_mut() {
+                        sess.remove_last_command();
+                    }
                 }
                 char_buffer.clear();
+                sent_command.clear();
+                typing = false;
+                continuing = false;
+                cancel_line(&handshake, &mut pty_stdin)?;
+                continue;
             }
-            // Enter key
-            b'\r' => {
-                // Normal line submission
+            // Enter key, `\n` separates the lines of a multi-line command being replayed
+            b'\r' | b'\n' if line_end => {
+                // Normal line submission, readline runs the whole line wherever the cursor is
+                char_buffer.move_to_end();
                 char_buffer.push_char(b'\r');
-                if let Some(sess) = session.as_mut() {
-                    sess.add_command(char_buffer.get_buf().to_vec());
+                timing.entered_ms = elapsed_ms();
+                let line = String::from_utf8_lossy(char_buffer.get_buf()).to_string();
+                if continuing {
+                    if let Some(sess) = session.as_mut() {
+                        sess.continue_last_command(&line);
+                        sess.set_command_timing(timing.clone());
+                        if !char_buffer.is_exact() {
+                            sess.mark_command_inexact();
+                        }
+                    }
+                    sent_command.push('\n');
+                    sent_command.push_str(line.trim_end_matches('\r'));
+                    char_buffer.clear();
+                } else {
+                    if let Some(sess) = session.as_mut() {
+                        sess.add_command(char_buffer.get_buf().to_vec());
+                        sess.set_command_timing(timing.clone());
+                        if let Some(cwd) = &shell_cwd {
+                            sess.set_command_cwd(cwd.clone());
+                        }
+                        if !char_buffer.is_exact() {
+                            sess.mark_command_inexact();
+                        }
+                    }
+
+                    // q + enter : quit without saving the session
+                    if char_buffer.get_buf() == b"q\r" {
+                        child.kill()?;
+                        session = None; // Don't save session
+                        break;
+                    }
+
+                    // Exit, the shell runs it and `run_internal` waits for it
+                    if exit_re.is_match(&line) {
+                        pty_stdin.write_all(&buf)?;
+                        pty_stdin.flush()?;
+                        break;
+                    }
+                    sent_command = line.trim_end_matches('\r').to_string();
+                    char_buffer.clear();
                 }
+            }
+
+            // Any other key edits the line
+            _ => char_buffer.feed(c),
+        }
+        if line_end {
+            // We sent a signal to indicate that we need to detect a NEW prompt.
+            // It must be sent before the `\r`, otherwise the prompt may be read before the signal.
+            expect_prompt(&handshake);
+        }
 
-                // q + enter : quit without saving the session
-                if char_buffer.get_buf() == b"q\r" {
-                    child.kill()?;
-                    session = None; // Don't save session
-                    break;
+        // Send input to PTY
+        pty_stdin.write_all(&buf)?;
+        pty_stdin.flush()?;
+
+        if line_end {
+            signal_handler.take_interrupted();
+            // We block the main thread, the keys typed meanwhile go to the running command.
+            // The channel is closed when the shell exited (e.g. `exit 1`) instead of showing a prompt.
+            let (received, keys_interrupted) = if forward_keys {
+                wait_prompt_forwarding_keys(&handshake.prompt, &mut pty_stdin)?
+            } else {
+                (handshake.prompt.recv().ok(), false)
+            };
+            let Some(info) = received else {
+                break;
+            };
+            let interrupted = signal_handler.take_interrupted() || keys_interrupted;
+            // The command goes on on the next line (heredoc, open quote, trailing `\`...)
+            continuing = info.continuation;
+            if continuing {
+                continue;
+            }
+            if info.cwd.is_some() {
+                shell_cwd = info.cwd;
+            }
+            timing.prompt_ms = elapsed_ms();
+            typing = false;
+            if let Some(sess) = session.as_mut() {
+                sess.set_command_duration(timing.prompt_ms - timing.entered_ms);
+                if interrupted {
+                    sess.mark_command_interrupted();
                 }
+            }
+
+            // The new prompt is displayed, so the command output is complete
+            if let (Some(sess), Some(capture)) = (session.as_mut(), output_capture.as_ref()) {
+                sess.add_command_output(capture.take());
+            }
+
+            let command_status = CommandStatus {
+                command: std::mem::take(&mut sent_command),
+                status: info.status,
+                interrupted,
+            };
+            let failed = command_status.failed();
+            statuses.push(command_status);
+            if failed && record_config.fail_fast {
+                exit_shell(&mut pty_stdin)?;
+                break;
+            }
+        }
+    }
+
+    Ok((session, statuses))
+}
+
+/// Like `handle_user_input`, but each key is sent to the shell as soon as it is typed,
+/// even while a command runs, so interactive programs (vim, `git add -p`, fzf...) get it.
+/// The keys are recorded with their timing. The lines typed at the prompt are still
+/// recorded as commands, without waiting for them to complete.
+// Precondition: Terminal is in raw mode
+fn handle_raw_input<R: Read, W: Write>(
+    mut user_input: R,
+    mut pty_stdin: W,
+    child: &mut ChildProc,
+    handshake: PromptHandshake,
+    record_config: &RecordConfig,
+    output_capture: Option<Arc<OutputCapture>>,
+) -> ReplayResult<(Option<Session>, Vec<CommandStatus>)> {
+    let mut buf = [0u8; 1];
+    let mut char_buffer = CharBuffer::new();
+    let exit_re = Regex::new(r"^\s*exit\s*$").unwrap();
+    let mut statuses: Vec<CommandStatus> = Vec::new();
+    let mut sent_command = String::new();
+    let mut interrupted = false;
+    let mut session = new_session(record_config)?;
+    // The keys are timed from the first prompt, which is also when the replay starts typing
+    let Ok(first_prompt) = handshake.prompt.recv() else {
+        return Ok((session, statuses));
+    };
+    let mut shell_cwd = first_prompt.cwd;
+    let start = Instant::now();
+    // The keys typed after a command was sent go to the program it runs
+    let mut at_prompt = true;
+    let mut continuing = false;
+    loop {
+        if child.try_wait()?.is_some() {
+            break;
+        }
+        let n = user_input.read(&mut buf)?;
+        if n == 0 {
+            break; // EOF
+        }
+        let c = buf[0];
+        if let Some(sess) = session.as_mut() {
+            sess.add_input(start.elapsed().as_millis() as u64, c);
+        }
 
-                // Exit
-                if exit_re.is_match(std::str::from_utf8(char_buffer.get_buf())?) {
-                    // We drop `pty_stdin` instead of `child` to ensure it close properly
-                    drop(pty_stdin);
-                    break;
+        // The prompt was displayed since the previous key
+        if !at_prompt {
+            if let Ok(info) = handshake.prompt.try_recv() {
+                at_prompt = true;
+                continuing = info.continuation;
+                if !continuing {
+                    if info.cwd.is_some() {
+                        shell_cwd = info.cwd;
+                    }
+                    if let Some(sess) = session.as_mut() {
+                        if interrupted {
+                            sess.mark_command_interrupted();
+                        }
+                        if let Some(capture) = output_capture.as_ref() {
+                            sess.add_command_output(capture.take());
+                        }
+                    }
+                    let command_status = CommandStatus {
+                        command: std::mem::take(&mut sent_command),
+                        status: info.status,
+                        interrupted: std::mem::take(&mut interrupted),
+                    };
+                    let failed = command_status.failed();
+                    statuses.push(command_status);
+                    if failed && record_config.fail_fast {
+                        exit_shell(&mut pty_stdin)?;
+                        break;
+                    }
                 }
-                char_buffer.clear();
             }
+        }
 
-            _ => {
-                char_buffer.push_char(c);
-            } // Any other character
+        if !at_prompt {
+            // Ctrl+C or Ctrl+\ typed while the command runs
+            interrupted |= matches!(c, b'\x03' | b'\x1c');
+        } else {
+            match c {
+                b'\x03' => {
+                    if continuing {
+                        if let Some(sess) = session.as_mut() {
+                            sess.remove_last_command();
+                        }
+                    }
+                    char_buffer.clear();
+                    sent_command.clear();
+                    continuing = false;
+                    cancel_line(&handshake, &mut pty_stdin)?;
+                    continue;
+                }
+                b'\r' | b'\n' if !char_buffer.is_pasting() => {
+                    char_buffer.move_to_end();
+                    char_buffer.push_char(b'\r');
+                    let line = String::from_utf8_lossy(char_buffer.get_buf()).to_string();
+                    if continuing {
+                        if let Some(sess) = session.as_mut() {
+                            sess.continue_last_command(&line);
+                            if !char_buffer.is_exact() {
+                                sess.mark_command_inexact();
+                            }
+                        }
+                        sent_command.push('\n');
+                        sent_command.push_str(line.trim_end_matches('\r'));
+                    } else {
+                        if let Some(sess) = session.as_mut() {
+                            sess.add_command(char_buffer.get_buf().to_vec());
+                            if let Some(cwd) = &shell_cwd {
+                                sess.set_command_cwd(cwd.clone());
+                            }
+                            if !char_buffer.is_exact() {
+                                sess.mark_command_inexact();
+                            }
+                        }
+                        if char_buffer.get_buf() == b"q\r" {
+                            child.kill()?;
+                            session = None;
+                            break;
+                        }
+                        if exit_re.is_match(&line) {
+                            pty_stdin.write_all(&buf)?;
+                            pty_stdin.flush()?;
+                            break;
+                        }
+                        sent_command = line.trim_end_matches('\r').to_string();
+                    }
+                    char_buffer.clear();
+                    expect_prompt(&handshake);
+                    at_prompt = false;
+                }
+                _ => char_buffer.feed(c),
+            }
         }
-        // Send input to PTY
+
         pty_stdin.write_all(&buf)?;
         pty_stdin.flush()?;
+    }
+
+    Ok((session, statuses))
+}
 
-        if buf[0] == b'\r' {
-            // We sent a signal to indicate that we need to detect a NEW prompt
-            command_sent_sender.send(()).unwrap();
+/// Tell the reader thread that the next prompt ends the command being sent.
+/// A prompt nobody waited for (e.g. the shell was slow to show it again) is dropped first.
+fn expect_prompt(handshake: &PromptHandshake) {
+    while handshake.prompt.try_recv().is_ok() {}
+    let _ = handshake.command_sent.try_send(());
+}
 
-            // We block the main thread
-            bash_ready_receiver.recv().unwrap();
+/// Cancel the line typed at the prompt with Ctrl+C, and wait for the prompt the shell shows
+/// again, otherwise it would be taken for the end of the next command.
+fn cancel_line<W: Write>(handshake: &PromptHandshake, pty_stdin: &mut W) -> ReplayResult<()> {
+    expect_prompt(handshake);
+    pty_stdin.write_all(b"\x03")?;
+    pty_stdin.flush()?;
+    // Shells not showing a new prompt should not block the recording
+    let _ = handshake.prompt.recv_timeout(Duration::from_secs(1));
+    Ok(())
+}
+
+/// Wait for the next prompt while sending the keys typed in the terminal to the running
+/// command, without recording them. Ctrl+C and Ctrl+\ are turned into signals by the PTY.
+/// Returns the prompt, None if the shell exited, and whether the command was interrupted.
+fn wait_prompt_forwarding_keys<W: Write>(
+    prompt: &Receiver<PromptInfo>,
+    pty_stdin: &mut W,
+) -> ReplayResult<(Option<PromptInfo>, bool)> {
+    let mut interrupted = false;
+    let mut keys = [0u8; 256];
+    loop {
+        match prompt.recv_timeout(Duration::from_millis(20)) {
+            Ok(info) => return Ok((Some(info), interrupted)),
+            Err(RecvTimeoutError::Disconnected) => return Ok((None, interrupted)),
+            Err(RecvTimeoutError::Timeout) => {}
         }
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fd` outlives the call
+        if unsafe { libc::poll(&mut fd, 1, 0) } != 1 || fd.revents & libc::POLLIN == 0 {
+            continue;
+        }
+        let n = TerminalReader.read(&mut keys)?;
+        if n == 0 {
+            continue;
+        }
+        let keys = &keys[..n];
+        interrupted |= keys.iter().any(|&c| matches!(c, b'\x03' | b'\x1c'));
+        pty_stdin.write_all(keys)?;
+        pty_stdin.flush()?;
+    }
+}
+
+/// Make the shell exit from its prompt, `run_internal` then waits for it.
+/// Only closing its input is not enough, the EOF may be read before readline is ready.
+fn exit_shell<W: Write>(pty_stdin: &mut W) -> ReplayResult<()> {
+    pty_stdin.write_all(b"exit\r")?;
+    pty_stdin.flush()?;
+    Ok(())
+}
+
+/// Give the shell some time to exit once the input is over, then kill it.
+/// Otherwise a shell or a program ignoring the end of its input would block `replay`.
+fn wait_shell_exit(child: &mut ChildProc) -> ReplayResult<()> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while child.try_wait()?.is_none() {
+        if Instant::now() >= deadline {
+            // It may have exited meanwhile
+            let _ = child.kill();
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
     }
+    Ok(())
+}
 
-    let session_saved = if let Some(sess) = session {
+// Precondition: Terminal is no longer in raw mode
+fn save_recorded_session<R: Read>(
+    session: Option<Session>,
+    user_input: &mut R,
+    record_config: &RecordConfig,
+) -> ReplayResult<Option<String>> {
+    let session_saved = if let Some(mut sess) = session {
+        if record_config.edit_description {
+            sess.description = prompt_session_description(user_input, sess.description)?;
+        }
+        for (name, value) in &record_config.parameters {
+            if sess.mark_parameter(name, value) == 0 {
+                eprintln!(
+                    "Value '{}' of parameter '{}' not found in the commands",
+                    value, name
+                );
+            }
+        }
+        if !sess.inexact.is_empty() {
+            eprintln!(
+                "{} command(s) typed with the history, the completion or keys not followed by replay may differ from what ran, check them with `replay edit`",
+                sess.inexact.len()
+            );
+        }
         sess.save_session(!record_config.no_compression)?;
+        if let Some(name) = &record_config.session_name {
+            SessionNameFile::set_name(name, &sess.id, false)?;
+        }
         Some("Session saved".to_string())
     } else if record_config.record_input {
         Some("No session saved".to_string())
@@ -223,17 +775,61 @@ fn handle_user_input<R: Read, W: Write>(
     Ok(session_saved)
 }
 
+/// Ask the user for a session description, keeping `current` on an empty answer
+fn prompt_session_description<R: Read>(
+    user_input: &mut R,
+    current: Option<String>,
+) -> ReplayResult<Option<String>> {
+    loop {
+        match &current {
+            Some(desc) => print!("Session description [{}]: ", desc),
+            None => print!("Session description (leave empty for none): "),
+        }
+        std::io::stdout().flush()?;
+
+        let (line, eof) = read_line(user_input)?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(current);
+        }
+        match args::validate_session_description(line) {
+            Ok(desc) => return Ok(Some(desc)),
+            Err(ReplayError::SessionError(err)) if eof => {
+                eprintln!("{}", err);
+                return Ok(current);
+            }
+            Err(ReplayError::SessionError(err)) => eprintln!("{}", err),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Read bytes until a line terminator, returning the line and whether EOF was reached
+fn read_line<R: Read>(user_input: &mut R) -> ReplayResult<(String, bool)> {
+    let mut line = Vec::new();
+    let mut buf = [0u8; 1];
+    loop {
+        if user_input.read(&mut buf)? == 0 {
+            return Ok((String::from_utf8_lossy(&line).to_string(), true));
+        }
+        match buf[0] {
+            b'\r' | b'\n' => return Ok((String::from_utf8_lossy(&line).to_string(), false)),
+            c => line.push(c),
+        }
+    }
+}
+
 fn read_from_pty<R: Read + Send, W: Write + Send>(
     mut pty_output: R,
     mut user_output: W,
-    bash_ready_sender: SyncSender<()>,
+    bash_ready_sender: SyncSender<PromptInfo>,
     command_sent_receiver: Receiver<()>,
-    ps1: char,
+    output_capture: Option<Arc<OutputCapture>>,
 ) -> ReplayResult<()> {
     let mut read_buf = [0u8; 1024];
-    let mut ps1_detected: bool = false;
-    let re_non_printable =
-        Regex::new(r"\x1b\[[0-9;?]*[a-zA-Z]|[\x01\x02]|\x1b\][^\x07]*\x07|\x1b\??\d*[hl]").unwrap();
+    let mut prompt_detected: bool = false;
+    let mut info_parser = PromptInfoParser::new();
+    let mut last_info = PromptInfo::default();
 
     loop {
         let n = pty_output.read(&mut read_buf)?;
@@ -241,24 +837,33 @@ fn read_from_pty<R: Read + Send, W: Write + Send>(
             break; // EOF
         }
 
-        // After the main thread sends a command, reset `ps1_detected` to false.
-        // The next detected prompt (ending with `ps1`) will then signal that Bash is ready.
+        // After the main thread sends a command, reset `prompt_detected` to false.
+        // The next prompt marker will then signal that the shell is ready.
         if command_sent_receiver.try_recv().is_ok() {
-            ps1_detected = false;
+            prompt_detected = false;
         };
 
-        user_output.write_all(&read_buf[..n])?;
-        user_output.flush()?;
+        // The status and the directory are printed right before the prompt
+        let output = info_parser.parse(&read_buf[..n], &mut last_info);
+        if !output.is_empty() {
+            user_output.write_all(&output)?;
+            user_output.flush()?;
 
-        let tail_vec: &Vec<u8> = &read_buf[..n].to_vec();
-        let tail_str = String::from_utf8_lossy(tail_vec);
-        let cleaned = re_non_printable
-            .replace_all(&tail_str, "")
-            .trim()
-            .to_string();
-        if cleaned.ends_with(&ps1.to_string()) && !ps1_detected {
-            let _ = bash_ready_sender.send(());
-            ps1_detected = true;
+            // Captured before signaling the prompt, so the main thread gets the whole output
+            if let Some(capture) = output_capture.as_ref() {
+                capture.push(&output);
+            }
+        }
+
+        if last_info.ready {
+            if prompt_detected {
+                // Nobody waits for this prompt, e.g. after Ctrl+C
+                last_info.ready = false;
+                last_info.continuation = false;
+            } else {
+                let _ = bash_ready_sender.send(std::mem::take(&mut last_info));
+                prompt_detected = true;
+            }
         }
     }
 
@@ -273,36 +878,49 @@ fn join_output_thread(output_thread: JoinHandle<ReplayResult<()>>) -> ReplayResu
     Ok(())
 }
 
-pub struct RawModeReader {
-    data: Vec<u8>,
-    pos: usize,
-    delay: Duration,
-}
+/// Unbuffered reader of the user terminal. `std::io::stdin()` keeps the keys read ahead
+/// (e.g. the rest of a paste) in its buffer: they would be sent after the keys forwarded
+/// to a running command, which are read from the terminal as well.
+pub struct TerminalReader;
 
-impl Default for RawModeReader {
-    fn default() -> Self {
-        Self {
-            data: Vec::new(),
-            pos: 0,
-            delay: Duration::from_millis(0),
+impl Read for TerminalReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            // SAFETY: `buf.len()` bytes can be written to `buf`
+            let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
         }
     }
 }
 
+#[derive(Default)]
+pub struct RawModeReader {
+    data: Vec<u8>,
+    pos: usize,
+    delays: Vec<Duration>, // delay before reading each byte of `data`
+}
+
 impl RawModeReader {
     pub fn with_input(input: &[u8]) -> Self {
-        Self {
-            data: input.to_vec(),
-            pos: 0,
-            delay: Duration::from_millis(0),
-        }
+        Self::with_input_and_delay(input, Duration::from_millis(0))
     }
 
     pub fn with_input_and_delay(input: &[u8], delay: Duration) -> Self {
+        Self::with_input_and_delays(input, vec![delay; input.len()])
+    }
+
+    pub fn with_input_and_delays(input: &[u8], delays: Vec<Duration>) -> Self {
+        assert_eq!(input.len(), delays.len(), "One delay is needed per byte");
         Self {
             data: input.to_vec(),
             pos: 0,
-            delay,
+            delays,
         }
     }
 }
@@ -312,7 +930,7 @@ impl std::io::Read for RawModeReader {
         if self.pos >= self.data.len() {
             return Ok(0);
         }
-        std::thread::sleep(self.delay);
+        std::thread::sleep(self.delays[self.pos]);
         buf[0] = self.data[self.pos];
         self.pos += 1;
         Ok(1)
@@ -325,6 +943,7 @@ mod test {
     use crate::paths::clear_replay_dir;
     use serial_test::serial;
     use std::io::sink;
+    use std::path::Path;
 
     /// Helper to run a fake session and return the list of recorded commands.
     fn run_and_get_commands(input: &[u8]) -> Vec<String> {
@@ -348,13 +967,550 @@ mod test {
     #[serial]
     fn record_commands_with_ctrl_c() {
         clear_replay_dir().unwrap();
-        let cmds = run_and_get_commands(b"echo test_ctrl_c\rsleep 5\r\x03exit\r");
+        let cmds = run_and_get_commands(b"echo test_ctrl_c\rech\x03for i in 1\r\x03exit\r");
 
         assert_eq!(
             cmds,
             vec!["echo test_ctrl_c\r", "exit\r"],
-            "Expected only echo and exit to be saved when Ctrl+C is used"
+            "Expected the lines cancelled with Ctrl+C not to be saved"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn record_interrupted_command() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            ..Default::default()
+        };
+        // Like Ctrl+C while the command runs
+        let interrupt = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(1000));
+            // SAFETY: sending a signal has no memory safety requirement
+            unsafe { libc::kill(libc::getpid(), libc::SIGINT) };
+        });
+        let reader = RawModeReader::with_input(b"sleep 5\rtrue\rexit\r");
+        let start = Instant::now();
+        let outcome = run_internal(reader, sink(), config).unwrap();
+        interrupt.join().unwrap();
+
+        assert!(
+            start.elapsed() < Duration::from_secs(4),
+            "sleep was interrupted"
+        );
+        assert_eq!(outcome.statuses[0].status, Some(130));
+        assert!(outcome.statuses[0].interrupted);
+        assert!(!outcome.statuses[1].interrupted);
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.commands, vec!["sleep 5\r", "true\r", "exit\r"]);
+        assert!(session.is_command_interrupted(0));
+        assert!(!session.is_command_interrupted(1));
+    }
+
+    #[test]
+    #[serial]
+    fn record_commands_edited_with_readline_keys() {
+        clear_replay_dir().unwrap();
+        let cmds = run_and_get_commands(
+            b"ech edited\x1b[D\x1b[D\x1b[D\x1b[D\x1b[D\x1b[D\x1b[Do\x05!\x7f\rexit\r",
         );
+
+        assert_eq!(cmds, vec!["echo edited\r", "exit\r"]);
+    }
+
+    #[test]
+    #[serial]
+    fn record_history_and_pasted_commands() {
+        clear_replay_dir().unwrap();
+        let cmds =
+            run_and_get_commands(b"echo one\r\x1b[A\r\x1b[200~echo a\recho b\x1b[201~\rexit\r");
+        assert_eq!(cmds, vec!["echo one\r", "\r", "echo a\necho b\r", "exit\r"]);
+
+        // The command recalled from the history is not known
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.inexact, vec![1]);
+    }
+
+    #[test]
+    #[serial]
+    fn record_multi_line_commands() {
+        clear_replay_dir().unwrap();
+        let cmds = run_and_get_commands(
+            b"cat <<EOF\rhello\rEOF\rfor i in 1 2\rdo echo $i\rdone\recho 'a\rb'\rls \\\r-d /\rexit\r",
+        );
+
+        assert_eq!(
+            cmds,
+            vec![
+                "cat <<EOF\nhello\nEOF\r",
+                "for i in 1 2\ndo echo $i\ndone\r",
+                "echo 'a\nb'\r",
+                "ls \\\n-d /\r",
+                "exit\r"
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn record_and_replay_raw_keys() {
+        clear_replay_dir().unwrap();
+        // `cat` reads the keys typed while it runs, until Ctrl+D
+        let input = b"cat\rhello\r\x04echo done\rexit\r";
+        let config = RecordConfig {
+            record_input: true,
+            record_output: true,
+            raw: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input_and_delay(input, Duration::from_millis(100));
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.commands, vec!["cat\r", "echo done\r", "exit\r"]);
+        let keys: Vec<u8> = session.input.iter().flat_map(|c| c.data.clone()).collect();
+        assert_eq!(keys, input);
+        assert!(session
+            .input
+            .windows(2)
+            .all(|w| w[0].time_ms < w[1].time_ms));
+        let output = session.get_command_output(0).unwrap();
+        let output: Vec<u8> = output.chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert!(String::from_utf8_lossy(&output).contains("hello\r\nhello"));
+
+        let config = RecordConfig {
+            headless: true,
+            raw: true,
+            ..Default::default()
+        };
+        let delays = session
+            .input
+            .iter()
+            .map(|c| Duration::from_millis(c.time_ms))
+            .scan(Duration::ZERO, |previous, time| {
+                Some(time - std::mem::replace(previous, time))
+            })
+            .collect();
+        let reader = RawModeReader::with_input_and_delays(&keys, delays);
+        let outcome = run_internal(reader, sink(), config).unwrap();
+        assert_eq!(
+            outcome.statuses,
+            vec![
+                CommandStatus {
+                    command: "cat".into(),
+                    status: Some(0),
+                    interrupted: false,
+                },
+                CommandStatus {
+                    command: "echo done".into(),
+                    status: Some(0),
+                    interrupted: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn run_multi_line_commands() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            headless: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(
+            b"cat <<EOF\nhello\nEOF\rfor i in 1 2\ndo (exit $i)\ndone\rexit\r",
+        );
+        let outcome = run_internal(reader, sink(), config).unwrap();
+
+        assert_eq!(
+            outcome.statuses,
+            vec![
+                CommandStatus {
+                    command: "cat <<EOF\nhello\nEOF".into(),
+                    status: Some(0),
+                    interrupted: false,
+                },
+                CommandStatus {
+                    command: "for i in 1 2\ndo (exit $i)\ndone".into(),
+                    status: Some(2),
+                    interrupted: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn prompt_detection_ignores_prompt_look_and_output() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            record_output: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(
+            b"PS1='multi\\nline % '\rprintf 'price: 5$'\rprintf '#'; sleep 0.2; false\rexit\r",
+        );
+        let outcome = run_internal(reader, sink(), config).unwrap();
+
+        let statuses: Vec<_> = outcome.statuses.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, vec![Some(0), Some(0), Some(1)]);
+        let session = Session::load_last_session().unwrap();
+        let output = |i: usize| -> String {
+            let output = session.get_command_output(i).unwrap();
+            let data: Vec<u8> = output.chunks.iter().flat_map(|c| c.data.clone()).collect();
+            String::from_utf8_lossy(&data).to_string()
+        };
+        assert!(output(1).contains("price: 5$"));
+        assert!(!output(1).contains("\x1b]6973;"), "Markers are stripped");
+    }
+
+    #[test]
+    #[serial]
+    fn record_commands_with_output() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            record_output: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"echo recorded_output\rexit\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        let output = session
+            .get_command_output(0)
+            .expect("The output of the first command should be recorded");
+        let data: Vec<u8> = output.chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert!(String::from_utf8_lossy(&data).contains("recorded_output\r\n"));
+        assert!(output
+            .chunks
+            .windows(2)
+            .all(|w| w[0].time_ms <= w[1].time_ms));
+    }
+
+    #[test]
+    #[serial]
+    fn record_in_directory_with_env() {
+        clear_replay_dir().unwrap();
+        let cwd = std::env::temp_dir().canonicalize().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            record_output: true,
+            cwd: Some(cwd.clone()),
+            env: BTreeMap::from([("REPLAY_TEST_VAR".into(), "from_env".into())]),
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"echo $REPLAY_TEST_VAR:$(pwd)\rexit\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.cwd, Some(cwd.clone()));
+        assert_eq!(session.get_command_cwd(0), Some(cwd.as_path()));
+        assert_eq!(session.env["REPLAY_TEST_VAR"], "from_env");
+        let output = session.get_command_output(0).unwrap();
+        let data: Vec<u8> = output.chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert!(String::from_utf8_lossy(&data).contains(&format!("from_env:{}", cwd.display())));
+    }
+
+    #[test]
+    #[serial]
+    fn record_directory_changes() {
+        clear_replay_dir().unwrap();
+        let cwd = std::env::temp_dir().canonicalize().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            cwd: Some(cwd.clone()),
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"cd /\rtrue\rcd 'replay dir test'\rexit\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.get_command_cwd(0), Some(cwd.as_path()));
+        assert_eq!(session.get_command_cwd(1), Some(Path::new("/")));
+        // A failing `cd` does not change the directory
+        assert_eq!(session.get_command_cwd(3), Some(Path::new("/")));
+    }
+
+    #[test]
+    #[serial]
+    fn record_command_timings() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            ..Default::default()
+        };
+        let reader =
+            RawModeReader::with_input_and_delay(b"sleep 0.3\rexit\r", Duration::from_millis(20));
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        let sleep = session.get_command_timing(0).unwrap();
+        assert!(sleep.idle_ms() >= 20);
+        // 9 more keys after the first one
+        assert!(sleep.typing_ms() >= 9 * 20);
+        assert_eq!(sleep.keys_ms.len(), 10);
+        assert!(sleep.keys_ms.windows(2).all(|keys| keys[1] >= keys[0] + 20));
+        assert!(sleep.duration_ms.unwrap() >= 300);
+        let exit = session.get_command_timing(1).unwrap();
+        assert!(exit.prompt_ms >= sleep.entered_ms + 300);
+        assert_eq!(
+            exit.duration_ms, None,
+            "The shell exits instead of showing a prompt"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn record_commands_without_output() {
+        clear_replay_dir().unwrap();
+        run_and_get_commands(b"echo not_recorded\rexit\r");
+
+        let session = Session::load_last_session().unwrap();
+        assert!(session.outputs.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn record_named_session() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            session_name: Some("named-session".into()),
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"ls\rexit\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(
+            SessionNameFile::get_session_id("named-session").unwrap(),
+            Some(session.id)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn commands_exit_statuses() {
+        let reader = RawModeReader::with_input(b"true\rfalse\r(exit 3)\rexit\r");
+        let outcome = run_internal(reader, sink(), RecordConfig::default()).unwrap();
+
+        let statuses: Vec<_> = outcome
+            .statuses
+            .iter()
+            .map(|s| (s.command.as_str(), s.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![("true", Some(0)), ("false", Some(1)), ("(exit 3)", Some(3))]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn fail_fast_stops_at_first_failure() {
+        let config = RecordConfig {
+            fail_fast: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"true\rfalse\recho never\rexit\r");
+        let outcome = run_internal(reader, sink(), config).unwrap();
+
+        assert_eq!(
+            outcome.statuses.len(),
+            2,
+            "No command should run after a failure"
+        );
+        assert!(outcome.statuses[1].failed());
+        assert_eq!(outcome.exit_code, 1, "bash exits with the last status");
+    }
+
+    #[test]
+    #[serial]
+    fn headless_returns_shell_exit_code() {
+        let config = RecordConfig {
+            headless: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"echo headless\rexit 4\r");
+        let outcome = run_internal(reader, sink(), config).unwrap();
+        assert_eq!(outcome.exit_code, 4);
+    }
+
+    #[test]
+    #[serial]
+    fn shell_ignoring_end_of_input_is_killed() {
+        let config = RecordConfig {
+            headless: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"set -o ignoreeof\r");
+        let start = Instant::now();
+        run_internal(reader, sink(), config).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    #[serial]
+    fn headless_pty_has_the_given_size() {
+        clear_replay_dir().unwrap();
+        let size = TerminalSize {
+            rows: 42,
+            cols: 132,
+        };
+        let config = RecordConfig {
+            record_input: true,
+            record_output: true,
+            headless: true,
+            size: Some(size),
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"stty size\rexit\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.size, Some(size));
+        let output = session.get_command_output(0).unwrap();
+        let data: Vec<u8> = output.chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert!(String::from_utf8_lossy(&data).contains("42 132\r\n"));
+    }
+
+    #[test]
+    fn prompt_info_parser_handles_split_markers() {
+        let mut parser = PromptInfoParser::new();
+        let mut parse = |chunk: &str| {
+            let mut info = PromptInfo::default();
+            parser.parse(chunk.as_bytes(), &mut info);
+            info
+        };
+        assert_eq!(parse("output\r\n\x1b]6973;12"), PromptInfo::default());
+        assert_eq!(parse("7\x07$ ").status, Some(127));
+        assert_eq!(parse("\x1b]0;title\x07$ "), PromptInfo::default());
+        assert_eq!(parse("\x1b]6973;0\x07$ \x1b]6973;1\x07$ ").status, Some(1));
+        assert_eq!(
+            parse("\x1b]7;file:///tmp/a b\x07\x1b]6973;0\x07$ "),
+            PromptInfo {
+                ready: true,
+                status: Some(0),
+                cwd: Some(PathBuf::from("/tmp/a b")),
+                continuation: false,
+            }
+        );
+        assert_eq!(
+            parse("\x1b]7;file://host/tmp/a%20b%C3%A9").cwd,
+            None,
+            "The path may be incomplete"
+        );
+        assert_eq!(parse("\x07$ ").cwd, Some(PathBuf::from("/tmp/a bé")));
+        assert!(parse("\r\n\x1b]6973;ps2\x07> ").continuation);
+        assert_eq!(parse("\x1b]6973;ps2\x07> ").status, None);
+        assert!(parse("\x1b]6973;\x07# ").ready, "sh reports no status");
+    }
+
+    #[test]
+    fn prompt_info_parser_strips_markers() {
+        let mut parser = PromptInfoParser::new();
+        let mut info = PromptInfo::default();
+        let mut parse = |chunk: &[u8]| parser.parse(chunk, &mut info);
+        assert_eq!(parse(b"out$\r\n\x1b]7;file:///tmp\x07\x1b]69"), b"out$\r\n");
+        assert_eq!(parse(b"73;0\x07\x1b[1m$ \x1b[0m"), b"\x1b[1m$ \x1b[0m");
+        // Other OSC sequences are shown, even when split
+        assert_eq!(parse(b"\x1b]0;tit"), b"\x1b]0;tit");
+        assert_eq!(parse(b"le\x07\x1b"), b"le\x07");
+        assert_eq!(parse(b"[31mred"), b"\x1b[31mred");
+        assert_eq!(parse(b"\x1b]6973;ps2\x07> "), b"> ");
+        assert_eq!(parse("été".as_bytes()), "été".as_bytes());
+    }
+
+    fn shell_available(shell: Shell) -> bool {
+        std::process::Command::new(shell.program())
+            .args(["-c", "exit 0"])
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    /// Record `true` then `false` in `shell`, returning the session and the statuses
+    fn record_in_shell(shell: Shell) -> Option<(Session, Vec<CommandStatus>)> {
+        if !shell_available(shell) {
+            eprintln!("{} is not available, skipping", shell);
+            return None;
+        }
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            shell,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"true\rfalse\rexit\r");
+        let outcome = run_internal(reader, sink(), config).unwrap();
+        Some((Session::load_last_session().unwrap(), outcome.statuses))
+    }
+
+    #[test]
+    #[serial]
+    fn record_in_bash() {
+        let (session, statuses) = record_in_shell(Shell::Bash).unwrap();
+        assert_eq!(session.shell, Shell::Bash);
+        assert_eq!(session.commands, vec!["true\r", "false\r", "exit\r"]);
+        assert_eq!(statuses[1].status, Some(1));
+    }
+
+    #[test]
+    #[serial]
+    fn record_in_sh() {
+        let Some((session, statuses)) = record_in_shell(Shell::Sh) else {
+            return;
+        };
+        assert_eq!(session.shell, Shell::Sh);
+        assert_eq!(session.commands, vec!["true\r", "false\r", "exit\r"]);
+        assert_eq!(statuses[1].status, None, "sh does not report statuses");
+    }
+
+    #[test]
+    #[serial]
+    fn record_multi_line_commands_in_sh() {
+        if !shell_available(Shell::Sh) {
+            return;
+        }
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            shell: Shell::Sh,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"for i in 1 2\rdo echo $i\rdone\rexit\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(
+            session.iter_commands().collect::<Vec<_>>(),
+            vec!["for i in 1 2\ndo echo $i\ndone\r", "exit\r"]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn record_in_zsh() {
+        let Some((session, statuses)) = record_in_shell(Shell::Zsh) else {
+            return;
+        };
+        assert_eq!(session.shell, Shell::Zsh);
+        assert_eq!(session.commands, vec!["true\r", "false\r", "exit\r"]);
+        assert_eq!(statuses[1].status, Some(1));
+    }
+
+    #[test]
+    #[serial]
+    fn record_in_fish() {
+        let Some((session, statuses)) = record_in_shell(Shell::Fish) else {
+            return;
+        };
+        assert_eq!(session.shell, Shell::Fish);
+        assert_eq!(session.commands, vec!["true\r", "false\r", "exit\r"]);
+        assert_eq!(statuses[1].status, Some(1));
     }
 
     #[test]
@@ -401,6 +1557,47 @@ mod test {
         );
     }
 
+    #[test]
+    #[serial]
+    fn record_with_description_edited_at_the_end() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            session_description: Some("initial description".into()),
+            no_compression: true,
+            edit_description: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"ls\rexit\rdescription set at the end\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(
+            session.description.as_deref(),
+            Some("description set at the end"),
+            "The description typed at the end should replace the initial one"
+        );
+        assert!(Session::get_session_path(&session.id, "json").exists());
+    }
+
+    #[test]
+    #[serial]
+    fn record_keeps_description_on_empty_answer() {
+        clear_replay_dir().unwrap();
+        let config = RecordConfig {
+            record_input: true,
+            session_description: Some("initial description".into()),
+            edit_description: true,
+            ..Default::default()
+        };
+        let reader = RawModeReader::with_input(b"ls\rexit\r\r");
+        run_internal(reader, sink(), config).unwrap();
+
+        let session = Session::load_last_session().unwrap();
+        assert_eq!(session.description.as_deref(), Some("initial description"));
+        assert!(Session::get_session_path(&session.id, "zst").exists());
+    }
+
     #[test]
     #[serial]
     fn record_exit_command_only() {
@@ -5,6 +5,9 @@ use chrono::Utc;
 /// We keep it *owned* to simplify usage where metadata comes from an iterator.
 pub struct DisplayMeta {
     pub index: usize,
+    pub names: Vec<String>,
+    /// Length after which the message is truncated
+    pub truncate: usize,
     pub meta: MetaData,
 }
 
@@ -18,9 +21,10 @@ impl std::fmt::Display for DisplayMeta {
             );
             write!(
                 f,
-                "replay@{{{}}}: {}",
+                "replay@{{{}}}{}: {}",
                 self.index,
-                Self::truncate_description(&list_message, 50)
+                self.format_names(),
+                Self::truncate_description(&list_message, self.truncate)
             )
         } else {
             let first_commands_stylized = self.meta.first_commands.join(" | ");
@@ -31,9 +35,10 @@ impl std::fmt::Display for DisplayMeta {
             );
             write!(
                 f,
-                "replay@{{{}}}: {}",
+                "replay@{{{}}}{}: {}",
                 self.index,
-                Self::truncate_description(&list_message, 50)
+                self.format_names(),
+                Self::truncate_description(&list_message, self.truncate)
             )
         }
     }
@@ -55,6 +60,14 @@ impl DisplayMeta {
         }
     }
 
+    fn format_names(&self) -> String {
+        if self.names.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", self.names.join(", "))
+        }
+    }
+
     fn truncate_description(line: &str, max_len: usize) -> String {
         let truncated: String = line.chars().take(max_len).collect();
         if line.chars().count() > max_len {
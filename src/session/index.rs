@@ -118,6 +118,17 @@ impl SessionIndexFile {
         let line_offset = Self::get_id_offset_by_index(index)?;
         Self::read_id_at(line_offset)
     }
+
+    /// Get the index of the most recent session whose id matches `predicate`
+    pub fn find_index<P: Fn(&str) -> bool>(predicate: P) -> ReplayResult<Option<u32>> {
+        for (index, id) in Self::iter_session_ids_rev()?.enumerate() {
+            if predicate(&id?) {
+                return Ok(Some(index as u32));
+            }
+        }
+        Ok(None)
+    }
+
     pub fn iter_session_ids_rev() -> ReplayResult<impl Iterator<Item = ReplayResult<String>>> {
         let file = SessionIndexFile::open_file()?;
         let iter = RevIndexIter::new(file, INDEX_SIZE)?;
@@ -1,18 +1,26 @@
+use crate::config;
 use crate::errors::ReplayResult;
 use crate::paths;
+use crate::shell::Shell;
 use chrono::Utc;
-use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde::de::{DeserializeOwned, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+mod bytes;
 mod display;
 pub mod index;
+mod names;
+pub mod params;
+pub mod search;
 
 pub use display::DisplayMeta;
 pub use index::SessionIndexFile;
-const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+pub use names::{SessionNameFile, SessionRef};
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct Session {
@@ -21,25 +29,227 @@ pub struct Session {
     pub timestamp: chrono::DateTime<Utc>,
     pub user: String,
     pub commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<CommandOutput>,
+    /// Shell the session was recorded in, bash for the sessions recorded before it was stored
+    #[serde(default)]
+    pub shell: Shell,
+    /// Directory the session was recorded in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// When each command was typed and how long it ran, while recording
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timings: Vec<CommandTiming>,
+    /// Directory each command was run in, reported by the shell while recording
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<CommandDirectory>,
+    /// Allowed environment variables, with their value when the session was recorded
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Index of the commands interrupted with Ctrl+C or Ctrl+\ while recording
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interrupted: Vec<usize>,
+    /// Size of the terminal when the recording started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<TerminalSize>,
+    /// Keys typed while recording with `--raw`, replayed as they are
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input: Vec<InputChunk>,
+    /// Index of the commands typed with the history, the completion or keys not followed,
+    /// their recorded text may differ from what the shell ran
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inexact: Vec<usize>,
+    /// Names of the parameters declared with `record --param` or `replay param`,
+    /// only their `{{name}}` placeholders are substituted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<String>,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl TerminalSize {
+    /// Size from the config, used when there is no terminal
+    pub fn from_config() -> ReplayResult<Self> {
+        let size = &config::get()?.pty;
+        Ok(Self {
+            rows: size.rows,
+            cols: size.cols,
+        })
+    }
+}
+
+impl std::fmt::Display for TerminalSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.cols, self.rows)
+    }
+}
+
+/// Terminal output captured while recording a command with `--with-output`.
+/// It holds everything the shell printed since the previous prompt,
+/// including the echo of the command and the next prompt.
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Index of the command in `Session::commands`
+    pub command: usize,
+    pub chunks: Vec<OutputChunk>,
+}
+
+/// Times are in milliseconds since the recording started
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandTiming {
+    /// Index of the command in `Session::commands`
+    pub command: usize,
+    /// When the prompt before the command was displayed
+    pub prompt_ms: u64,
+    /// When the first key of the command was typed
+    pub typed_ms: u64,
+    /// When Enter was pressed
+    pub entered_ms: u64,
+    /// Time from Enter to the next prompt, None if the shell exited
+    pub duration_ms: Option<u64>,
+    /// When each key of the command was typed, since `typed_ms`, Enter included
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys_ms: Vec<u64>,
+}
+
+impl CommandTiming {
+    /// Time spent before typing the command
+    pub fn idle_ms(&self) -> u64 {
+        self.typed_ms.saturating_sub(self.prompt_ms)
+    }
+
+    /// Time spent typing the command
+    pub fn typing_ms(&self) -> u64 {
+        self.entered_ms.saturating_sub(self.typed_ms)
+    }
+
+    /// Time between each of the `count + 1` bytes of the command, following the rhythm of
+    /// the recorded keys. When keys were edited, the pauses are picked along the typing and
+    /// scaled to last as long. Without recorded keys, typing is spread evenly.
+    pub fn typing_pauses_ms(&self, count: usize) -> Vec<u64> {
+        let pauses: Vec<u64> = self
+            .keys_ms
+            .windows(2)
+            .map(|keys| keys[1].saturating_sub(keys[0]))
+            .collect();
+        if pauses.len() == count {
+            return pauses;
+        }
+        if pauses.is_empty() {
+            return vec![self.typing_ms() / count.max(1) as u64; count];
+        }
+        let picked: Vec<u64> = (0..count)
+            .map(|i| pauses[i * pauses.len() / count])
+            .collect();
+        let total = picked.iter().sum::<u64>().max(1);
+        picked
+            .into_iter()
+            .map(|pause| pause * self.typing_ms() / total)
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandDirectory {
+    /// Index of the command in `Session::commands`
+    pub command: usize,
+    pub cwd: PathBuf,
+}
+
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutputChunk {
+    /// Milliseconds elapsed since the recording started
+    pub time_ms: u64,
+    #[serde(with = "bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Bytes typed at the same time while recording with `--raw`
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputChunk {
+    /// Milliseconds elapsed since the first prompt was displayed
+    pub time_ms: u64,
+    #[serde(with = "bytes")]
+    pub data: Vec<u8>,
+}
+
 #[derive(Deserialize, Debug)]
+#[serde(from = "RawMetaData")]
 pub struct MetaData {
+    pub id: String,
     pub description: Option<String>,
     pub timestamp: chrono::DateTime<Utc>,
-    #[serde(rename = "commands", deserialize_with = "first_two_commands")]
+    pub user: String,
     pub first_commands: Vec<String>,
+    /// Number of commands, without the final `exit`
+    pub command_count: usize,
+}
+
+/// Fields of a session file read to build a `MetaData`
+#[derive(Deserialize)]
+struct RawMetaData {
+    id: String,
+    description: Option<String>,
+    timestamp: chrono::DateTime<Utc>,
+    #[serde(default)]
+    user: String,
+    commands: CommandsSummary,
 }
 
-fn first_two_commands<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let all: Vec<String> = Vec::deserialize(deserializer)?;
-    Ok(all
-        .into_iter()
-        .take(2)
-        .map(|cmd| cmd.replace("\r", ""))
-        .collect())
+impl From<RawMetaData> for MetaData {
+    fn from(raw: RawMetaData) -> Self {
+        Self {
+            id: raw.id,
+            description: raw.description,
+            timestamp: raw.timestamp,
+            user: raw.user,
+            first_commands: raw.commands.first,
+            command_count: raw.commands.count,
+        }
+    }
+}
+
+/// First two commands and number of commands, without keeping the whole list in memory
+struct CommandsSummary {
+    first: Vec<String>,
+    count: usize,
+}
+
+impl<'de> Deserialize<'de> for CommandsSummary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SummaryVisitor;
+
+        impl<'de> Visitor<'de> for SummaryVisitor {
+            type Value = CommandsSummary;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list of commands")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut summary = CommandsSummary {
+                    first: Vec::new(),
+                    count: 0,
+                };
+                let mut last_is_exit = false;
+                while let Some(cmd) = seq.next_element::<String>()? {
+                    last_is_exit = cmd.trim() == "exit";
+                    if summary.first.len() < 2 {
+                        summary.first.push(cmd.replace('\r', "").replace('\n', " "));
+                    }
+                    summary.count += 1;
+                }
+                summary.count -= usize::from(last_is_exit);
+                Ok(summary)
+            }
+        }
+
+        deserializer.deserialize_seq(SummaryVisitor)
+    }
 }
 
 impl Session {
@@ -48,6 +258,17 @@ impl Session {
         let timestamp = Utc::now();
         Ok(Self {
             commands: Vec::new(),
+            outputs: Vec::new(),
+            shell: Shell::default(),
+            cwd: None,
+            timings: Vec::new(),
+            directories: Vec::new(),
+            env: BTreeMap::new(),
+            interrupted: Vec::new(),
+            size: None,
+            input: Vec::new(),
+            inexact: Vec::new(),
+            parameters: Vec::new(),
             id: Self::generate_id(&description, &timestamp, &user),
             description,
             timestamp,
@@ -76,8 +297,146 @@ impl Session {
             .push(String::from_utf8_lossy(&cmd_raw).to_string());
     }
 
+    /// Add a line to the last command, when the shell asked for its continuation.
+    /// The lines of a multi-line command are separated by `\n`.
+    pub fn continue_last_command(&mut self, line: &str) {
+        let Some(cmd) = self.commands.last_mut() else {
+            return;
+        };
+        if cmd.ends_with('\r') {
+            cmd.pop();
+        }
+        cmd.push('\n');
+        cmd.push_str(line);
+    }
+
     pub fn remove_last_command(&mut self) -> Option<String> {
-        self.commands.pop()
+        let cmd = self.commands.pop()?;
+        let index = self.commands.len();
+        self.outputs.retain(|output| output.command != index);
+        self.directories.retain(|dir| dir.command != index);
+        self.timings.retain(|timing| timing.command != index);
+        self.interrupted.retain(|&command| command != index);
+        self.inexact.retain(|&command| command != index);
+        Some(cmd)
+    }
+
+    /// Mark the last recorded command as interrupted
+    pub fn mark_command_interrupted(&mut self) {
+        if let Some(command) = self.commands.len().checked_sub(1) {
+            if !self.interrupted.contains(&command) {
+                self.interrupted.push(command);
+            }
+        }
+    }
+
+    pub fn is_command_interrupted(&self, index: usize) -> bool {
+        self.interrupted.contains(&index)
+    }
+
+    /// Mark the last recorded command as possibly different from what the shell ran
+    pub fn mark_command_inexact(&mut self) {
+        if let Some(command) = self.commands.len().checked_sub(1) {
+            if !self.inexact.contains(&command) {
+                self.inexact.push(command);
+            }
+        }
+    }
+
+    pub fn is_command_inexact(&self, index: usize) -> bool {
+        self.inexact.contains(&index)
+    }
+
+    /// Attach the captured output to the last recorded command
+    pub fn add_command_output(&mut self, chunks: Vec<OutputChunk>) {
+        if chunks.is_empty() || self.commands.is_empty() {
+            return;
+        }
+        let command = self.commands.len() - 1;
+        match self.outputs.iter_mut().find(|o| o.command == command) {
+            Some(output) => output.chunks.extend(chunks),
+            None => self.outputs.push(CommandOutput { command, chunks }),
+        }
+    }
+
+    /// Record a key typed `time_ms` after the first prompt
+    pub fn add_input(&mut self, time_ms: u64, byte: u8) {
+        match self.input.last_mut() {
+            Some(chunk) if chunk.time_ms == time_ms => chunk.data.push(byte),
+            _ => self.input.push(InputChunk {
+                time_ms,
+                data: vec![byte],
+            }),
+        }
+    }
+
+    /// Whether the keys were recorded with `--raw`, to be replayed instead of the commands
+    pub fn is_raw(&self) -> bool {
+        !self.input.is_empty()
+    }
+
+    /// Set the timing of the last recorded command, its index is filled in
+    pub fn set_command_timing(&mut self, timing: CommandTiming) {
+        let Some(command) = self.commands.len().checked_sub(1) else {
+            return;
+        };
+        self.timings.retain(|timing| timing.command != command);
+        self.timings.push(CommandTiming { command, ..timing });
+    }
+
+    /// Set how long the last recorded command ran
+    pub fn set_command_duration(&mut self, duration_ms: u64) {
+        let command = self.commands.len().wrapping_sub(1);
+        if let Some(timing) = self.timings.iter_mut().find(|t| t.command == command) {
+            timing.duration_ms = Some(duration_ms);
+        }
+    }
+
+    pub fn get_command_timing(&self, index: usize) -> Option<&CommandTiming> {
+        self.timings.iter().find(|timing| timing.command == index)
+    }
+
+    /// Set the directory the last recorded command is run in
+    pub fn set_command_cwd(&mut self, cwd: PathBuf) {
+        let Some(command) = self.commands.len().checked_sub(1) else {
+            return;
+        };
+        self.directories.retain(|dir| dir.command != command);
+        self.directories.push(CommandDirectory { command, cwd });
+    }
+
+    pub fn get_command_cwd(&self, index: usize) -> Option<&Path> {
+        self.directories
+            .iter()
+            .find(|dir| dir.command == index)
+            .map(|dir| dir.cwd.as_path())
+    }
+
+    pub fn get_command_output(&self, index: usize) -> Option<&CommandOutput> {
+        self.outputs.iter().find(|output| output.command == index)
+    }
+
+    /// Replace the commands by script lines, as given by `iter_script_commands`.
+    /// The session still ends with `exit` and keeps the output of the unchanged commands.
+    /// The keys recorded with `--raw` are dropped if any command changed.
+    pub fn set_script_commands(&mut self, lines: Vec<String>) {
+        let previous = std::mem::take(&mut self.commands);
+        self.commands = lines
+            .into_iter()
+            .map(|line| format!("{}\r", line))
+            .collect();
+        self.commands.push(String::from("exit\r"));
+        let commands = &self.commands;
+        let unchanged = |index: usize| previous.get(index) == commands.get(index);
+        self.outputs.retain(|output| unchanged(output.command));
+        self.directories.retain(|dir| unchanged(dir.command));
+        self.timings.retain(|timing| unchanged(timing.command));
+        self.interrupted.retain(|&command| unchanged(command));
+        // The commands written by the user are the ones to run
+        self.inexact.retain(|&command| unchanged(command));
+        if previous != self.commands {
+            self.input.clear();
+        }
     }
 
     pub fn get_last_command(&self) -> Option<&String> {
@@ -85,21 +444,24 @@ impl Session {
     }
 
     fn load_from_files<T: DeserializeOwned>(session_id: &str) -> ReplayResult<T> {
-        // Try compressed .zst first
+        let (path, compressed) = Session::get_stored_path(session_id)?;
+        let file = File::open(path)?;
+        let data = if compressed {
+            serde_json::from_reader(zstd::Decoder::new(file)?)?
+        } else {
+            serde_json::from_reader(BufReader::new(file))?
+        };
+        Ok(data)
+    }
+
+    /// Path of the file the session is stored in, and whether it is compressed
+    pub fn get_stored_path(session_id: &str) -> ReplayResult<(PathBuf, bool)> {
+        // Try compressed .zst first, then fallback to plain .json
         let zst_path = Session::get_session_path(session_id, "zst");
         if zst_path.try_exists()? {
-            let file = File::open(zst_path)?;
-            let decoder = zstd::Decoder::new(file)?;
-            let data = serde_json::from_reader(decoder)?;
-            return Ok(data);
+            return Ok((zst_path, true));
         }
-
-        // Fallback to plain .json
-        let json_path = Session::get_session_path(session_id, "json");
-        let file = File::open(json_path)?;
-        let reader = BufReader::new(file);
-        let data = serde_json::from_reader(reader)?;
-        Ok(data)
+        Ok((Session::get_session_path(session_id, "json"), false))
     }
 
     pub fn load_session_by_index(index: u32) -> ReplayResult<Self> {
@@ -116,29 +478,36 @@ impl Session {
     }
 
     pub fn save_session(&self, compress: bool) -> ReplayResult<()> {
+        self.write_session_file(compress)?;
+        SessionIndexFile::push_session(&self.id)?;
+        Ok(())
+    }
+
+    /// Write back a session that was already saved, keeping its compression
+    pub fn update_session(&self) -> ReplayResult<()> {
+        let (_, compressed) = Self::get_stored_path(&self.id)?;
+        self.write_session_file(compressed)
+    }
+
+    fn write_session_file(&self, compress: bool) -> ReplayResult<()> {
         if compress {
             let file = std::fs::File::create(Self::get_session_path(&self.id, "zst"))?;
-            let mut encoder = zstd::Encoder::new(file, DEFAULT_COMPRESSION_LEVEL)?;
-            serde_json::to_writer_pretty(&mut encoder, &self)?;
+            let mut encoder = zstd::Encoder::new(file, config::get()?.record.compression_level)?;
+            serde_json::to_writer(&mut encoder, &self)?;
             encoder.finish()?;
         } else {
-            let json = serde_json::to_string_pretty(&self)?;
+            // Not pretty-printed, the captured output would span a line per chunk
+            let json = serde_json::to_string(&self)?;
             std::fs::write(Self::get_session_path(&self.id, "json"), json)?;
         }
-
-        SessionIndexFile::push_session(&self.id)?;
         Ok(())
     }
 
     pub fn remove_session_by_index(index: u32) -> ReplayResult<()> {
         let session_id = SessionIndexFile::remove_session_id(index)?;
-        let zst_path = Session::get_session_path(&session_id, "zst");
-        if zst_path.try_exists()? {
-            std::fs::remove_file(zst_path)?;
-        } else {
-            let json_session_path = Session::get_session_path(&session_id, "json");
-            std::fs::remove_file(json_session_path)?;
-        }
+        SessionNameFile::remove_session(&session_id)?;
+        let (path, _) = Session::get_stored_path(&session_id)?;
+        std::fs::remove_file(path)?;
         Ok(())
     }
 
@@ -146,10 +515,34 @@ impl Session {
         Self::remove_session_by_index(0)
     }
 
+    /// Remove all the sessions, with the index and the names pointing to them.
+    /// The config in the same directory is kept.
+    pub fn remove_all_sessions() -> ReplayResult<()> {
+        let sessions = paths::session_dir();
+        std::fs::remove_dir_all(sessions)?;
+        for path in [SessionIndexFile::get_path(), SessionNameFile::get_path()] {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn iter_commands(&self) -> impl Iterator<Item = &str> {
         // We use impl Iterator to not have to declare RecordedCommand public
         self.commands.iter().map(|s| s.as_str())
     }
+
+    /// Iterate over the commands as they would be written in a script:
+    /// without the trailing `\r` and without the final `exit`
+    pub fn iter_script_commands(&self) -> impl Iterator<Item = &str> {
+        let ends_with_exit = self.commands.last().is_some_and(|cmd| cmd.trim() == "exit");
+        let count = self.commands.len() - usize::from(ends_with_exit);
+        self.commands
+            .iter()
+            .take(count)
+            .map(|s| s.trim_end_matches('\r'))
+    }
     pub fn get_session_path(id: &str, extension: &str) -> PathBuf {
         paths::session_dir().join(format!("{}.{}", id, extension))
     }
@@ -173,6 +566,7 @@ pub mod tests {
 
     pub fn setup() {
         let _ = std::fs::remove_file(SessionIndexFile::get_path());
+        let _ = std::fs::remove_file(SessionNameFile::get_path());
     }
 
     #[test]
@@ -198,6 +592,22 @@ pub mod tests {
         assert!(std::path::Path::new(&Session::get_session_path(&session.id, "zst")).exists());
     }
 
+    #[test]
+    #[serial]
+    fn test_session_update() {
+        setup();
+        let mut session = Session::new(Some("test session update".into())).unwrap();
+        session.save_session(false).unwrap();
+        session.add_command("ls\r".into());
+        session.update_session().unwrap();
+
+        // Still stored uncompressed and only once in the index
+        assert!(!Session::get_session_path(&session.id, "zst").exists());
+        let loaded = Session::load_last_session().unwrap();
+        assert_eq!(loaded.commands, vec!["ls\r"]);
+        assert!(SessionIndexFile::get_session_id(1).is_err());
+    }
+
     #[test]
     #[serial]
     fn test_session_index_file() {
@@ -229,6 +639,232 @@ pub mod tests {
         ));
     }
 
+    #[test]
+    fn test_command_output() {
+        let mut session = Session::new(None).unwrap();
+        let chunk = |time_ms: u64, data: &[u8]| OutputChunk {
+            time_ms,
+            data: data.to_vec(),
+        };
+
+        // No command recorded yet, the output is dropped
+        session.add_command_output(vec![chunk(0, b"$ ")]);
+        assert!(session.outputs.is_empty());
+
+        session.add_command("echo 1\r".into());
+        session.add_command_output(vec![chunk(10, b"1\r\n")]);
+        session.add_command("echo 2\r".into());
+        session.add_command_output(vec![chunk(20, b"2\r\n")]);
+        session.add_command_output(vec![chunk(25, b"$ ")]);
+        assert_eq!(session.get_command_output(0).unwrap().chunks.len(), 1);
+        assert_eq!(session.get_command_output(1).unwrap().chunks.len(), 2);
+
+        // Removing a command also removes its output
+        session.remove_last_command();
+        assert!(session.get_command_output(1).is_none());
+        assert_eq!(
+            session.get_command_output(0).unwrap().chunks,
+            vec![chunk(10, b"1\r\n")]
+        );
+    }
+
+    #[test]
+    fn test_continue_last_command() {
+        let mut session = Session::new(None).unwrap();
+        session.continue_last_command("ignored\r");
+        assert!(session.commands.is_empty());
+
+        session.add_command("for i in 1 2\r".into());
+        session.continue_last_command("do echo $i\r");
+        session.continue_last_command("done\r");
+        assert_eq!(session.commands, vec!["for i in 1 2\ndo echo $i\ndone\r"]);
+        assert_eq!(
+            session.iter_script_commands().collect::<Vec<_>>(),
+            vec!["for i in 1 2\ndo echo $i\ndone"]
+        );
+    }
+
+    #[test]
+    fn test_set_script_commands() {
+        let mut session = Session::new(None).unwrap();
+        for cmd in ["ls\r", "ech 1\r", "exit\r"] {
+            session.add_command(cmd.into());
+            session.add_command_output(vec![OutputChunk {
+                time_ms: 0,
+                data: cmd.as_bytes().to_vec(),
+            }]);
+        }
+
+        session.set_script_commands(vec!["ls".into(), "echo 1".into(), "pwd".into()]);
+        assert_eq!(
+            session.commands,
+            vec!["ls\r", "echo 1\r", "pwd\r", "exit\r"]
+        );
+        // The output of the edited command and of the moved `exit` are outdated
+        assert!(session.get_command_output(0).is_some());
+        assert!(session.get_command_output(1).is_none());
+        assert!(session.get_command_output(2).is_none());
+        assert!(session.get_command_output(3).is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_remove_all_sessions() {
+        setup();
+        let session = Session::new(None).unwrap();
+        session.save_session(true).unwrap();
+        SessionNameFile::set_name("deploy", &session.id, false).unwrap();
+        let config_path = config::Config::get_path();
+        std::fs::write(&config_path, "[record]\n").unwrap();
+
+        Session::remove_all_sessions().unwrap();
+        assert!(Session::load_last_session().is_err());
+        assert!(!SessionNameFile::contains("deploy").unwrap());
+        assert!(config_path.exists());
+        std::fs::remove_file(config_path).unwrap();
+    }
+
+    #[test]
+    fn test_raw_input() {
+        let mut session = Session::new(None).unwrap();
+        assert!(!session.is_raw());
+        for (time_ms, byte) in [(0, b'v'), (0, b'i'), (5, b'\r'), (900, b'\x1b')] {
+            session.add_input(time_ms, byte);
+        }
+        assert!(session.is_raw());
+        assert_eq!(
+            session.input,
+            vec![
+                InputChunk {
+                    time_ms: 0,
+                    data: b"vi".to_vec()
+                },
+                InputChunk {
+                    time_ms: 5,
+                    data: b"\r".to_vec()
+                },
+                InputChunk {
+                    time_ms: 900,
+                    data: b"\x1b".to_vec()
+                },
+            ]
+        );
+
+        session.add_command("vi\r".into());
+        session.add_command("exit\r".into());
+        session.set_script_commands(vec!["vi".into()]);
+        assert!(session.is_raw());
+        // The keys don't match the commands anymore
+        session.set_script_commands(vec!["vim".into()]);
+        assert!(!session.is_raw());
+    }
+
+    #[test]
+    fn test_iter_script_commands() {
+        let mut session = Session::new(None).unwrap();
+        assert_eq!(session.iter_script_commands().count(), 0);
+
+        session.add_command("ls\r".into());
+        session.add_command("echo exit\r".into());
+        assert_eq!(
+            session.iter_script_commands().collect::<Vec<_>>(),
+            vec!["ls", "echo exit"]
+        );
+
+        session.add_command("  exit \r".into());
+        assert_eq!(
+            session.iter_script_commands().collect::<Vec<_>>(),
+            vec!["ls", "echo exit"]
+        );
+    }
+
+    #[test]
+    fn test_load_session_without_outputs() {
+        // Sessions recorded before output capture existed must still load
+        let json = r#"{
+            "description": null,
+            "id": "abc",
+            "timestamp": "2025-01-01T00:00:00Z",
+            "user": "user",
+            "commands": ["ls\r", "exit\r"]
+        }"#;
+        let session: Session = serde_json::from_str(json).unwrap();
+        assert_eq!(session.commands.len(), 2);
+        assert!(session.outputs.is_empty());
+        assert_eq!(session.shell, Shell::Bash);
+
+        // And sessions without output are saved the same way as before
+        let saved = serde_json::to_string(&session).unwrap();
+        assert!(!saved.contains("outputs"));
+        assert!(saved.contains(r#""shell":"bash""#));
+    }
+
+    #[test]
+    fn test_chunk_bytes_as_base64() {
+        let mut session = Session::new(None).unwrap();
+        session.add_command("ls\r".into());
+        session.add_command_output(vec![OutputChunk {
+            time_ms: 12,
+            data: b"a\r\n\x1b[0m".to_vec(),
+        }]);
+        session.add_input(0, b'l');
+        let saved = serde_json::to_string(&session).unwrap();
+        assert!(saved.contains(r#"{"time_ms":12,"data":"YQ0KG1swbQ=="}"#));
+        assert!(saved.contains(r#"{"time_ms":0,"data":"bA=="}"#));
+
+        let loaded: Session = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.outputs, session.outputs);
+        assert_eq!(loaded.input, session.input);
+
+        // Sessions saved before store the bytes as arrays of numbers
+        let json = r#"{
+            "description": null,
+            "id": "abc",
+            "timestamp": "2025-01-01T00:00:00Z",
+            "user": "user",
+            "commands": ["ls\r"],
+            "outputs": [{"command": 0, "chunks": [{"time_ms": 12, "data": [104, 105]}]}]
+        }"#;
+        let loaded: Session = serde_json::from_str(json).unwrap();
+        assert_eq!(loaded.outputs[0].chunks[0].data, b"hi");
+    }
+
+    #[test]
+    #[serial]
+    fn test_session_names() {
+        setup();
+        let session1 = Session::new(Some("test session1".into())).unwrap();
+        let session2 = Session::new(Some("test session2".into())).unwrap();
+        session1.save_session(true).unwrap();
+        session2.save_session(true).unwrap();
+
+        SessionNameFile::set_name("deploy", &session1.id, false).unwrap();
+        SessionNameFile::set_name("staging", &session1.id, false).unwrap();
+        assert_eq!(
+            SessionNameFile::get_names(&session1.id).unwrap(),
+            vec!["deploy", "staging"]
+        );
+        assert!(matches!(
+            SessionNameFile::set_name("deploy", &session2.id, false),
+            Err(ReplayError::SessionError(_))
+        ));
+
+        let by_name = SessionRef::Name("deploy".into());
+        assert_eq!(by_name.resolve_index().unwrap(), 1);
+        let by_prefix = SessionRef::Name(session2.id[..12].to_string());
+        assert_eq!(by_prefix.resolve_index().unwrap(), 0);
+        assert!(SessionRef::Name("unknown".into()).resolve_index().is_err());
+
+        // The name moves with the session when the indices shift
+        Session::remove_session_by_index(0).unwrap();
+        assert_eq!(by_name.resolve_index().unwrap(), 0);
+
+        // And it is removed along with its session
+        Session::remove_session_by_index(0).unwrap();
+        assert!(!SessionNameFile::contains("deploy").unwrap());
+        assert!(SessionNameFile::get_names(&session1.id).unwrap().is_empty());
+    }
+
     #[test]
     #[serial]
     fn test_session_remove() {
@@ -0,0 +1,144 @@
+use super::SessionIndexFile;
+use crate::errors::{ReplayError, ReplayResult};
+use crate::paths;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Minimum length of an id prefix to be resolved, shorter ones are too likely to be ambiguous
+const MIN_ID_PREFIX_LEN: usize = 4;
+
+/// Reference to a session given on the command line
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SessionRef {
+    /// Stash-like index, `replay@{index}`
+    Index(u32),
+    /// Session name, or prefix of a session id
+    Name(String),
+}
+
+impl SessionRef {
+    /// Resolve the reference to the current index of the session
+    pub fn resolve_index(&self) -> ReplayResult<u32> {
+        match self {
+            SessionRef::Index(index) => Ok(*index),
+            SessionRef::Name(name) => {
+                if let Some(id) = SessionNameFile::get_session_id(name)? {
+                    return SessionIndexFile::find_index(|session_id| session_id == id)?
+                        .ok_or_else(|| {
+                            ReplayError::SessionError(format!(
+                                "Session '{}' no longer exists",
+                                name
+                            ))
+                        });
+                }
+                Self::resolve_id_prefix(name)
+            }
+        }
+    }
+
+    fn resolve_id_prefix(prefix: &str) -> ReplayResult<u32> {
+        let unknown = || ReplayError::SessionError(format!("Unknown session '{}'", prefix));
+        if prefix.len() < MIN_ID_PREFIX_LEN || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(unknown());
+        }
+
+        let mut matches = Vec::new();
+        for (index, id) in SessionIndexFile::iter_session_ids_rev()?.enumerate() {
+            if id?.starts_with(prefix) {
+                matches.push(index as u32);
+            }
+        }
+        match matches.as_slice() {
+            [] => Err(unknown()),
+            [index] => Ok(*index),
+            _ => Err(ReplayError::SessionError(format!(
+                "Session id prefix '{}' is ambiguous",
+                prefix
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for SessionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionRef::Index(index) => write!(f, "replay@{{{}}}", index),
+            SessionRef::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Table of the session names, stored next to the index file.
+/// Several names can point to the same session.
+pub struct SessionNameFile;
+
+impl SessionNameFile {
+    pub(super) fn get_path() -> PathBuf {
+        paths::replay_dir().join("session_names")
+    }
+
+    fn load() -> ReplayResult<BTreeMap<String, String>> {
+        let path = Self::get_path();
+        if !path.try_exists()? {
+            return Ok(BTreeMap::new());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(names: &BTreeMap<String, String>) -> ReplayResult<()> {
+        std::fs::write(Self::get_path(), serde_json::to_string_pretty(names)?)?;
+        Ok(())
+    }
+
+    pub fn get_session_id(name: &str) -> ReplayResult<Option<String>> {
+        Ok(Self::load()?.remove(name))
+    }
+
+    pub fn contains(name: &str) -> ReplayResult<bool> {
+        Ok(Self::load()?.contains_key(name))
+    }
+
+    /// Give `name` to the session, moving it from another session only if `force` is set
+    pub fn set_name(name: &str, session_id: &str, force: bool) -> ReplayResult<()> {
+        let mut names = Self::load()?;
+        if !force && names.contains_key(name) {
+            return Err(ReplayError::SessionError(format!(
+                "Session name '{}' already exists",
+                name
+            )));
+        }
+        names.insert(name.to_string(), session_id.to_string());
+        Self::save(&names)
+    }
+
+    pub fn remove_name(name: &str) -> ReplayResult<()> {
+        let mut names = Self::load()?;
+        if names.remove(name).is_none() {
+            return Err(ReplayError::SessionError(format!(
+                "Unknown session name '{}'",
+                name
+            )));
+        }
+        Self::save(&names)
+    }
+
+    /// Remove all the names of a session
+    pub fn remove_session(session_id: &str) -> ReplayResult<()> {
+        let mut names = Self::load()?;
+        let count = names.len();
+        names.retain(|_, id| id != session_id);
+        if names.len() != count {
+            Self::save(&names)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_names(session_id: &str) -> ReplayResult<Vec<String>> {
+        Ok(Self::load()?
+            .into_iter()
+            .filter(|(_, id)| id == session_id)
+            .map(|(name, _)| name)
+            .collect())
+    }
+}
@@ -0,0 +1,154 @@
+//! Parameters of a session: placeholders such as `{{env}}` written in the
+//! recorded commands, substituted with actual values before replaying them.
+
+use super::Session;
+use crate::errors::{ReplayError, ReplayResult};
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap());
+
+/// Check that `name` can be used in a `{{name}}` placeholder
+pub fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Session {
+    /// Replace every occurrence of `value` in the commands by the `{{name}}` placeholder,
+    /// and declare the parameter. Only whole words are replaced: `st` is not found in `status`.
+    /// Return the number of commands changed.
+    pub fn mark_parameter(&mut self, name: &str, value: &str) -> usize {
+        if value.is_empty() {
+            return 0;
+        }
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let value_re = Regex::new(&format!(
+            "{}{}{}",
+            if value.starts_with(is_word) {
+                r"\b"
+            } else {
+                ""
+            },
+            regex::escape(value),
+            if value.ends_with(is_word) { r"\b" } else { "" }
+        ))
+        .unwrap();
+        let placeholder = format!("{{{{{}}}}}", name);
+        let mut changed = 0;
+        for cmd in self
+            .commands
+            .iter_mut()
+            .filter(|cmd| value_re.is_match(cmd))
+        {
+            *cmd = value_re
+                .replace_all(cmd, regex::NoExpand(&placeholder))
+                .into_owned();
+            changed += 1;
+        }
+        if changed > 0 && !self.parameters.iter().any(|p| p == name) {
+            self.parameters.push(name.to_string());
+        }
+        changed
+    }
+
+    /// Replace the placeholders of the declared parameters by their value, all of them
+    /// must be given. Other `{{...}}` (Helm, Jinja, Go templates) are kept as they are.
+    pub fn substitute_parameters(&mut self, values: &BTreeMap<String, String>) -> ReplayResult<()> {
+        if let Some(missing) = self.parameters.iter().find(|p| !values.contains_key(*p)) {
+            return Err(ReplayError::SessionError(format!(
+                "No value given for parameter '{}'",
+                missing
+            )));
+        }
+        for cmd in self.commands.iter_mut() {
+            *cmd = PLACEHOLDER
+                .replace_all(cmd, |caps: &Captures| match values.get(&caps[1]) {
+                    Some(value) if self.parameters.iter().any(|p| p == &caps[1]) => value.clone(),
+                    _ => caps[0].to_string(),
+                })
+                .into_owned();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(commands: &[&str]) -> Session {
+        let mut session = Session::new(None).unwrap();
+        for cmd in commands {
+            session.add_command(cmd.as_bytes().to_vec());
+        }
+        session
+    }
+
+    #[test]
+    fn substitute() {
+        let mut session = session(&[
+            "ssh {{host}}\r",
+            "git checkout {{ branch }}\r",
+            "kubectl get pods -o go-template='{{ range .items }}{{ end }}'\r",
+        ]);
+        session.parameters = vec!["host".into(), "branch".into()];
+        let mut values = BTreeMap::from([("host".to_string(), "staging".to_string())]);
+        assert!(matches!(
+            session.substitute_parameters(&values),
+            Err(ReplayError::SessionError(_))
+        ));
+
+        values.insert("branch".into(), "main".into());
+        values.insert("end".into(), "not a parameter".into());
+        session.substitute_parameters(&values).unwrap();
+        assert_eq!(
+            session.commands,
+            vec![
+                "ssh staging\r",
+                "git checkout main\r",
+                "kubectl get pods -o go-template='{{ range .items }}{{ end }}'\r"
+            ]
+        );
+    }
+
+    #[test]
+    fn mark() {
+        let mut session = session(&["ssh staging\r", "ls\r", "scp a staging:staging/\r"]);
+        assert_eq!(session.mark_parameter("env", "staging"), 2);
+        assert_eq!(
+            session.commands,
+            vec!["ssh {{env}}\r", "ls\r", "scp a {{env}}:{{env}}/\r"]
+        );
+        assert_eq!(session.mark_parameter("env", ""), 0);
+        assert_eq!(session.mark_parameter("dir", "/tmp"), 0);
+        assert_eq!(session.parameters, vec!["env"]);
+    }
+
+    #[test]
+    fn mark_whole_words() {
+        let mut session = session(&["git status\r", "deploy st st.conf /st/$1\r"]);
+        assert_eq!(session.mark_parameter("env", "st"), 1);
+        assert_eq!(
+            session.commands,
+            vec!["git status\r", "deploy {{env}} {{env}}.conf /{{env}}/$1\r"]
+        );
+        assert_eq!(session.mark_parameter("arg", "/$1"), 1);
+        assert_eq!(
+            session.commands[1],
+            "deploy {{env}} {{env}}.conf /{{env}}{{arg}}\r"
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("env"));
+        assert!(is_valid_name("_build_2"));
+        assert!(!is_valid_name("2env"));
+        assert!(!is_valid_name("my-env"));
+        assert!(!is_valid_name("a}}{{b"));
+        assert!(!is_valid_name(""));
+    }
+}
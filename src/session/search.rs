@@ -0,0 +1,159 @@
+//! Search of a pattern through all the recorded sessions.
+
+use super::{CommandOutput, Session, SessionIndexFile};
+use crate::errors::ReplayResult;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::LazyLock;
+
+static ANSI_ESCAPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b(\[[0-9;?]*[ -/]*[@-~]|\][^\x07\x1b]*(\x07|\x1b\\)|.)").unwrap()
+});
+
+/// Part of a session searched without the output, which is by far its largest field
+#[derive(Deserialize)]
+struct SessionText {
+    description: Option<String>,
+    commands: Vec<String>,
+}
+
+/// Part of a session searched with the output
+#[derive(Deserialize)]
+struct SessionTextWithOutput {
+    #[serde(flatten)]
+    text: SessionText,
+    #[serde(default)]
+    outputs: Vec<CommandOutput>,
+}
+
+/// Session in which the pattern was found
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub index: usize,
+    pub id: String,
+    pub description: Option<String>,
+    pub description_matches: bool,
+    /// Matching commands, as (command index, command)
+    pub commands: Vec<(usize, String)>,
+    /// Matching output lines, as (command index, line)
+    pub output_lines: Vec<(usize, String)>,
+}
+
+impl Session {
+    /// Search `pattern` in the description, the commands and, if `with_output`, the output
+    /// of all the sessions, from the last recorded one
+    pub fn search(pattern: &Regex, with_output: bool) -> ReplayResult<Vec<SearchMatch>> {
+        let mut matches = Vec::new();
+        for (index, id) in SessionIndexFile::iter_session_ids_rev()?.enumerate() {
+            let id = id?;
+            let (text, outputs) = if with_output {
+                let session: SessionTextWithOutput = Session::load_from_files(&id)?;
+                (session.text, session.outputs)
+            } else {
+                (Session::load_from_files::<SessionText>(&id)?, Vec::new())
+            };
+
+            let found = SearchMatch {
+                index,
+                description_matches: text
+                    .description
+                    .as_deref()
+                    .is_some_and(|desc| pattern.is_match(desc)),
+                commands: text
+                    .commands
+                    .iter()
+                    .map(|cmd| cmd.trim_end_matches('\r'))
+                    .enumerate()
+                    .filter(|(_, cmd)| pattern.is_match(cmd))
+                    .map(|(i, cmd)| (i, cmd.to_string()))
+                    .collect(),
+                output_lines: Self::search_outputs(pattern, &outputs),
+                description: text.description,
+                id,
+            };
+            if found.description_matches
+                || !found.commands.is_empty()
+                || !found.output_lines.is_empty()
+            {
+                matches.push(found);
+            }
+        }
+        Ok(matches)
+    }
+
+    fn search_outputs(pattern: &Regex, outputs: &[CommandOutput]) -> Vec<(usize, String)> {
+        let mut lines = Vec::new();
+        for output in outputs {
+            let data: Vec<u8> = output
+                .chunks
+                .iter()
+                .flat_map(|chunk| chunk.data.iter().copied())
+                .collect();
+            let text = String::from_utf8_lossy(&data);
+            let text = ANSI_ESCAPE.replace_all(&text, "");
+            // The first line is the echo of the command, already searched
+            for line in text.lines().skip(1) {
+                let line = line.trim_end_matches('\r');
+                if pattern.is_match(line) {
+                    lines.push((output.command, line.to_string()));
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::setup;
+    use crate::session::OutputChunk;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn search_sessions() {
+        setup();
+        let mut session_1 = Session::new(Some("deploy to kubernetes".into())).unwrap();
+        session_1.add_command("kubectl get pods\r".into());
+        session_1.add_command_output(vec![OutputChunk {
+            time_ms: 0,
+            data: b"kubectl get pods\r\n\x1b[1mNAME\x1b[0m  web-kube-1\r\n$ ".to_vec(),
+        }]);
+        session_1.add_command("exit\r".into());
+        session_1.save_session(true).unwrap();
+        let mut session_2 = Session::new(None).unwrap();
+        session_2.add_command("ls\r".into());
+        session_2.save_session(false).unwrap();
+
+        let pattern = Regex::new("kube").unwrap();
+        let matches = Session::search(&pattern, false).unwrap();
+        assert_eq!(
+            matches,
+            vec![SearchMatch {
+                index: 1,
+                id: session_1.id.clone(),
+                description: Some("deploy to kubernetes".into()),
+                description_matches: true,
+                commands: vec![(0, "kubectl get pods".into())],
+                output_lines: vec![],
+            }]
+        );
+
+        let matches = Session::search(&pattern, true).unwrap();
+        assert_eq!(
+            matches[0].output_lines,
+            vec![(0, "NAME  web-kube-1".into())]
+        );
+
+        let pattern = Regex::new("^ls$").unwrap();
+        let matches = Session::search(&pattern, true).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].index, 0);
+        assert!(!matches[0].description_matches);
+
+        assert!(Session::search(&Regex::new("nothing").unwrap(), true)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! # Shell
+//!
+//! Shells that can be spawned in the PTY, and how each of them is set up
+//! so `replay` can detect its prompt and the exit status of the commands:
+//! an invisible OSC marker is printed before each prompt, whatever the prompt looks like.
+
+use crate::errors::ReplayResult;
+use crate::paths;
+use clap::ValueEnum;
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+
+/// bash startup file: the usual ones, then the exit status of each command and the current
+/// directory (OSC 7) are printed before the prompt as OSC sequences, which terminals ignore.
+/// The continuation prompt (PS2) is marked the same way.
+const BASH_RCFILE: &str = r#"[ -f /etc/bash.bashrc ] && . /etc/bash.bashrc
+[ -f ~/.bashrc ] && . ~/.bashrc
+__replay_status() { local status=$?; printf '\033]7;file://%s\007\033]6973;%s\007' "$PWD" "$status"; return $status; }
+PROMPT_COMMAND="__replay_status;${PROMPT_COMMAND}"
+PS2="\[\033]6973;ps2\007\]${PS2}"
+"#;
+
+/// zsh reads its startup files from `$ZDOTDIR`, so ours load the user ones
+/// from the original directory before adding the status hook.
+const ZSH_ZSHENV: &str = r#"[ -f "${REPLAY_ZDOTDIR:-$HOME}/.zshenv" ] && . "${REPLAY_ZDOTDIR:-$HOME}/.zshenv"
+"#;
+const ZSH_ZSHRC: &str = r#"ZDOTDIR="${REPLAY_ZDOTDIR:-$HOME}"
+[ -f "$ZDOTDIR/.zshrc" ] && . "$ZDOTDIR/.zshrc"
+__replay_status() { printf '\033]7;file://%s\007\033]6973;%s\007' "$PWD" "$?" }
+precmd_functions=(__replay_status $precmd_functions)
+PS2=$'%{\e]6973;ps2\a%}'"${PS2}"
+"#;
+
+/// sh startup file, read from `$ENV`: the user one, then the prompts are marked.
+/// sh has no hook to report the exit status, so the marker of PS1 has no value.
+const SH_ENV: &str = r#"[ -n "$REPLAY_ENV" ] && . "$REPLAY_ENV"
+PS1="$(printf '\033]6973;\007')${PS1}"
+PS2="$(printf '\033]6973;ps2\007')${PS2}"
+"#;
+
+/// fish edits multi-line commands itself and has no continuation prompt
+const FISH_INIT: &str = r#"function __replay_status --on-event fish_prompt; printf '\e]7;file://%s\a\e]6973;%s\a' $PWD $status; end"#;
+
+#[derive(ValueEnum, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    #[default]
+    Bash,
+    Zsh,
+    /// POSIX sh, the exit status of the commands is not available
+    Sh,
+    Fish,
+}
+
+impl Shell {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Shell::Bash => "/bin/bash",
+            Shell::Zsh => "zsh",
+            Shell::Sh => "/bin/sh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// Build the command spawning the shell with the status hook installed
+    pub fn command(&self) -> ReplayResult<CommandBuilder> {
+        let mut cmd = CommandBuilder::new(self.program());
+        match self {
+            Shell::Bash => {
+                let rcfile = paths::replay_dir().join("bashrc");
+                std::fs::write(&rcfile, BASH_RCFILE)?;
+                cmd.arg("--rcfile");
+                cmd.arg(rcfile);
+            }
+            Shell::Zsh => {
+                let zdotdir = paths::replay_dir().join("zsh");
+                std::fs::create_dir_all(&zdotdir)?;
+                std::fs::write(zdotdir.join(".zshenv"), ZSH_ZSHENV)?;
+                std::fs::write(zdotdir.join(".zshrc"), ZSH_ZSHRC)?;
+                if let Some(original) = std::env::var_os("ZDOTDIR") {
+                    cmd.env("REPLAY_ZDOTDIR", original);
+                }
+                cmd.env("ZDOTDIR", zdotdir);
+            }
+            Shell::Sh => {
+                let env_file = paths::replay_dir().join("shrc");
+                std::fs::write(&env_file, SH_ENV)?;
+                if let Some(original) = std::env::var_os("ENV") {
+                    cmd.env("REPLAY_ENV", original);
+                }
+                cmd.env("ENV", env_file);
+                cmd.arg("-i");
+            }
+            Shell::Fish => {
+                cmd.arg("--init-command");
+                cmd.arg(FISH_INIT);
+            }
+        }
+        Ok(cmd)
+    }
+}
+
+impl std::fmt::Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Sh => "sh",
+            Shell::Fish => "fish",
+        };
+        write!(f, "{}", name)
+    }
+}
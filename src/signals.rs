@@ -0,0 +1,144 @@
+//! # Signals
+//!
+//! Signals received by `replay` while a session runs are forwarded to the foreground
+//! process group of the PTY, as a terminal would do, and the terminal of the user is
+//! restored however `replay` exits.
+
+use crate::errors::ReplayResult;
+use crate::pty::pty_size;
+use crate::session::TerminalSize;
+use crossterm::terminal;
+use portable_pty::MasterPty;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGTSTP, SIGWINCH};
+use signal_hook::flag;
+use signal_hook::iterator::{Handle, Signals};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+
+type Master = Box<dyn MasterPty + Send>;
+
+/// Raw mode of the user terminal, disabled when dropped, even on errors and panics
+pub struct RawMode;
+
+impl RawMode {
+    pub fn enable() -> ReplayResult<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Err(err) = terminal::disable_raw_mode() {
+            eprintln!("Impossible to quit the raw mode: {}", err);
+        }
+    }
+}
+
+/// Restore the terminal before printing the panic message
+pub fn restore_terminal_on_panic() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = terminal::disable_raw_mode();
+        previous(info);
+    }));
+}
+
+/// Signals forwarded to the PTY while a session runs
+const FORWARDED: [i32; 5] = [SIGINT, SIGQUIT, SIGTSTP, SIGTERM, SIGHUP];
+
+/// Whether the forwarded signals have their default action (e.g. Ctrl+C ends `replay`).
+/// Once registered, the handlers are never removed, so the default action is emulated
+/// when no session runs.
+fn set_default_actions(enabled: bool) -> ReplayResult<()> {
+    static DEFAULT_ACTIONS: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    if let Some(default_actions) = DEFAULT_ACTIONS.get() {
+        default_actions.store(enabled, Ordering::SeqCst);
+        return Ok(());
+    }
+    let default_actions = Arc::new(AtomicBool::new(enabled));
+    for signal in FORWARDED {
+        flag::register_conditional_default(signal, default_actions.clone())?;
+    }
+    let _ = DEFAULT_ACTIONS.set(default_actions);
+    Ok(())
+}
+
+/// Thread handling the signals received while the shell runs in a PTY.
+/// The signals get their default action again once it is dropped.
+pub struct SignalHandler {
+    handle: Handle,
+    thread: Option<JoinHandle<()>>,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl SignalHandler {
+    /// Forward the signals to the foreground process group of `master`, and resize it
+    /// like the terminal on SIGWINCH if `resize` is set.
+    /// SIGTERM and SIGHUP hang up the shell (`shell_pid`) and its jobs, then `replay` exits.
+    pub fn start(master: Master, shell_pid: Option<u32>, resize: bool) -> ReplayResult<Self> {
+        set_default_actions(false)?;
+        let mut signals = Signals::new(FORWARDED)?;
+        if resize {
+            signals.add_signal(SIGWINCH)?;
+        }
+        let handle = signals.handle();
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let thread_interrupted = interrupted.clone();
+        let thread = thread::spawn(move || {
+            for signal in signals.forever() {
+                match signal {
+                    SIGWINCH => {
+                        if let Ok((cols, rows)) = terminal::size() {
+                            let _ = master.resize(pty_size(TerminalSize { rows, cols }));
+                        }
+                    }
+                    SIGTERM | SIGHUP => {
+                        Self::forward(master.as_ref(), SIGHUP);
+                        if let Some(pid) = shell_pid {
+                            // SAFETY: sending a signal has no memory safety requirement
+                            unsafe { libc::kill(pid as libc::pid_t, SIGHUP) };
+                        }
+                        let _ = terminal::disable_raw_mode();
+                        std::process::exit(128 + signal);
+                    }
+                    _ => {
+                        Self::forward(master.as_ref(), signal);
+                        if signal != SIGTSTP {
+                            thread_interrupted.store(true, Ordering::SeqCst);
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            handle,
+            thread: Some(thread),
+            interrupted,
+        })
+    }
+
+    /// Send `signal` to the foreground process group of the PTY
+    fn forward(master: &(dyn MasterPty + Send), signal: i32) {
+        if let Some(group) = master.process_group_leader().filter(|&group| group > 0) {
+            // SAFETY: sending a signal has no memory safety requirement
+            unsafe { libc::kill(-group, signal) };
+        }
+    }
+
+    /// Whether Ctrl+C or Ctrl+\ was forwarded since the last call
+    pub fn take_interrupted(&self) -> bool {
+        self.interrupted.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for SignalHandler {
+    fn drop(&mut self) {
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = set_default_actions(true);
+    }
+}
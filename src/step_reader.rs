@@ -0,0 +1,201 @@
+use crossterm::style::{PrintStyledContent, Stylize};
+use crossterm::{cursor, queue, terminal};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// What to do with the next command of the session
+#[derive(Debug, PartialEq, Eq)]
+enum StepAction {
+    Run,
+    Skip,
+    Edit,
+    Quit,
+}
+
+/// Reader used by `replay run --step`.
+/// Before each command, it shows the command and waits for a key on `keys`.
+/// Since `handle_user_input` only reads the next byte once bash displayed a new prompt,
+/// a command is only offered when the previous one is over.
+pub struct StepReader<R: Read, W: Write> {
+    keys: R,
+    hint_output: W,
+    commands: VecDeque<String>,
+    total: usize,
+    pending: VecDeque<u8>,
+    editing: bool,
+    finished: bool,
+    delay: Duration,
+}
+
+impl<R: Read, W: Write> StepReader<R, W> {
+    pub fn new<'a>(
+        keys: R,
+        hint_output: W,
+        commands: impl Iterator<Item = &'a str>,
+        delay: Duration,
+    ) -> Self {
+        let commands: VecDeque<String> = commands.map(String::from).collect();
+        Self {
+            keys,
+            hint_output,
+            total: commands.len(),
+            commands,
+            pending: VecDeque::new(),
+            editing: false,
+            finished: false,
+            delay,
+        }
+    }
+
+    fn ask(&mut self, cmd: &str) -> std::io::Result<StepAction> {
+        let step = self.total - self.commands.len();
+        let hint = format!(
+            "{}  [{}/{}] Enter: run, s: skip, e: edit, q: quit",
+            cmd, step, self.total
+        );
+        queue!(
+            self.hint_output,
+            cursor::SavePosition,
+            PrintStyledContent(hint.dark_grey())
+        )?;
+        self.hint_output.flush()?;
+
+        let mut key = [0u8; 1];
+        let action = loop {
+            if self.keys.read(&mut key)? == 0 {
+                break StepAction::Quit;
+            }
+            match key[0] {
+                b'\r' | b'\n' => break StepAction::Run,
+                b's' => break StepAction::Skip,
+                b'e' => break StepAction::Edit,
+                b'q' => break StepAction::Quit,
+                _ => {} // Ignore any other key
+            }
+        };
+
+        queue!(
+            self.hint_output,
+            cursor::RestorePosition,
+            terminal::Clear(terminal::ClearType::UntilNewLine)
+        )?;
+        self.hint_output.flush()?;
+        Ok(action)
+    }
+
+    /// The shell is always left with `exit`, otherwise it would never stop
+    fn finish(&mut self) {
+        self.commands.clear();
+        self.pending.extend(b"exit\r");
+        self.finished = true;
+    }
+}
+
+impl<R: Read, W: Write> Read for StepReader<R, W> {
+    // Like `RawModeReader`, we return 1 byte at a time
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(c) = self.pending.pop_front() {
+                std::thread::sleep(self.delay);
+                buf[0] = c;
+                return Ok(1);
+            }
+
+            if self.editing {
+                // Keys are forwarded to bash until the edited command is sent
+                if self.keys.read(&mut buf[..1])? == 0 {
+                    buf[0] = b'\r';
+                }
+                if buf[0] == b'\r' || buf[0] == b'\n' {
+                    buf[0] = b'\r';
+                    self.editing = false;
+                }
+                return Ok(1);
+            }
+
+            if self.finished {
+                return Ok(0);
+            }
+
+            let Some(cmd) = self.commands.pop_front() else {
+                self.finish();
+                continue;
+            };
+            match self.ask(&cmd)? {
+                StepAction::Run => {
+                    self.pending.extend(cmd.as_bytes());
+                    self.pending.push_back(b'\r');
+                }
+                StepAction::Skip => {}
+                StepAction::Edit => {
+                    self.pending.extend(cmd.as_bytes());
+                    self.editing = true;
+                }
+                StepAction::Quit => self.finish(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::sink;
+
+    fn read_all(commands: &[&str], keys: &[u8]) -> String {
+        let mut reader = StepReader::new(keys, sink(), commands.iter().copied(), Duration::ZERO);
+        let mut sent = Vec::new();
+        reader.read_to_end(&mut sent).unwrap();
+        String::from_utf8(sent).unwrap()
+    }
+
+    #[test]
+    fn run_all_commands() {
+        assert_eq!(read_all(&["ls", "pwd"], b"\r\r"), "ls\rpwd\rexit\r");
+    }
+
+    #[test]
+    fn skip_command() {
+        assert_eq!(read_all(&["ls", "pwd"], b"s\r"), "pwd\rexit\r");
+    }
+
+    #[test]
+    fn edit_command() {
+        assert_eq!(
+            read_all(&["ls", "date"], b"\re -u\r"),
+            "ls\rdate -u\rexit\r"
+        );
+    }
+
+    #[test]
+    fn quit_before_the_end() {
+        assert_eq!(read_all(&["ls", "pwd", "date"], b"\rq"), "ls\rexit\r");
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        assert_eq!(read_all(&["ls"], b"xyz\r"), "ls\rexit\r");
+    }
+
+    #[test]
+    fn end_of_keys_quits() {
+        assert_eq!(read_all(&["ls", "pwd"], b"\r"), "ls\rexit\r");
+    }
+
+    #[test]
+    fn hint_shows_the_command() {
+        let mut hints = Vec::new();
+        let mut reader = StepReader::new(
+            &b"\r"[..],
+            &mut hints,
+            ["echo step"].into_iter(),
+            Duration::ZERO,
+        );
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        drop(reader);
+        let hints = String::from_utf8_lossy(&hints);
+        assert!(hints.contains("echo step"));
+        assert!(hints.contains("[1/1]"));
+    }
+}
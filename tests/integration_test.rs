@@ -62,3 +62,725 @@ fn test_list_after_drop_session() {
         .assert()
         .stdout(predicates::str::contains(session_desc2).not());
 }
+
+#[test]
+#[serial]
+fn test_record_with_description() {
+    let session_desc = format!("recorded {}", &Uuid::new_v4().to_string()[..10]);
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("record")
+        .arg(&session_desc)
+        .arg("--no-compression")
+        .write_stdin("echo test\rexit\r")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Session saved"));
+
+    let session = Session::load_last_session().unwrap();
+    assert_eq!(session.description.as_deref(), Some(session_desc.as_str()));
+    assert!(Session::get_session_path(&session.id, "json").exists());
+
+    let pattern = format!(
+        r"(?m)^replay@\{{0\}}: .*, message: {}$",
+        session_desc.replace("-", r"\-")
+    );
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("list")
+        .assert()
+        .stdout(predicates::str::is_match(pattern).unwrap());
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_record_with_edited_description() {
+    let session_desc = format!("edited {}", &Uuid::new_v4().to_string()[..10]);
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("record")
+        .arg("--edit")
+        .write_stdin(format!("echo test\rexit\r{}\n", session_desc))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Session saved"));
+
+    let pattern = format!(
+        r"(?m)^replay@\{{0\}}: .*, message: {}$",
+        session_desc.replace("-", r"\-")
+    );
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("list")
+        .assert()
+        .stdout(predicates::str::is_match(pattern).unwrap());
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_export_asciicast() {
+    let session_desc = format!("export {}", &Uuid::new_v4().to_string()[..10]);
+    let mut session = Session::new(Some(session_desc.clone())).unwrap();
+    session.add_command("echo test\r".into());
+    session.save_session(true).unwrap();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("export")
+        .arg("--format")
+        .arg("asciicast")
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::starts_with("{")
+                .and(predicates::str::contains(r#""version":2"#))
+                .and(predicates::str::contains(session_desc))
+                .and(predicates::str::contains(r#""o","e"]"#)),
+        );
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_export_sh() {
+    let session_desc = format!("script {}", &Uuid::new_v4().to_string()[..10]);
+    let mut session = Session::new(Some(session_desc.clone())).unwrap();
+    session.add_command("echo exported\r".into());
+    session.add_command("exit\r".into());
+    session.save_session(true).unwrap();
+
+    let script_path = std::env::temp_dir().join(format!("{}.sh", Uuid::new_v4()));
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("export")
+        .arg("--format")
+        .arg("sh")
+        .arg("--strict")
+        .arg("-o")
+        .arg(&script_path)
+        .assert()
+        .success();
+
+    let script = std::fs::read_to_string(&script_path).unwrap();
+    assert!(script.starts_with("#!/usr/bin/env bash\n"));
+    assert!(script.contains(&format!("# {}\n", session_desc)));
+    assert!(script.contains("set -euo pipefail\n"));
+    assert!(script.ends_with("\necho exported\n"));
+
+    // The exported script can be run directly
+    Command::new(&script_path)
+        .assert()
+        .success()
+        .stdout("exported\n");
+
+    std::fs::remove_file(&script_path).unwrap();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_import_history_range() {
+    let session_desc = format!("imported {}", &Uuid::new_v4().to_string()[..10]);
+    let history_path = std::env::temp_dir().join(format!("{}_history", Uuid::new_v4()));
+    std::fs::write(
+        &history_path,
+        "#1700000000\nls\n#1700000001\necho first\n#1700000002\necho second\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .arg(&history_path)
+        .arg("--history")
+        .arg("--from")
+        .arg("2")
+        .arg("-d")
+        .arg(&session_desc)
+        .assert()
+        .success()
+        .stdout("Imported 2 commands in replay@{0}\n");
+    std::fs::remove_file(&history_path).unwrap();
+
+    let session = Session::load_last_session().unwrap();
+    assert_eq!(session.description.as_deref(), Some(session_desc.as_str()));
+    assert_eq!(
+        session.iter_commands().collect::<Vec<_>>(),
+        vec!["echo first\r", "echo second\r", "exit\r"]
+    );
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_import_from_stdin() {
+    let marker = Uuid::new_v4().to_string();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin(format!("#!/bin/sh\n# comment\necho {}\n", marker))
+        .assert()
+        .success();
+
+    let session = Session::load_last_session().unwrap();
+    assert_eq!(
+        session.iter_script_commands().collect::<Vec<_>>(),
+        vec![format!("echo {}", marker)]
+    );
+
+    // Nothing to import
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin("# only a comment\n")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No command to import"));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_tag_and_run_by_name() {
+    let name = format!("tag-{}", &Uuid::new_v4().to_string()[..8]);
+    let marker = Uuid::new_v4().to_string();
+    let mut session = Session::new(None).unwrap();
+    session.add_command(format!("echo {}\r", marker).into_bytes());
+    session.add_command("exit\r".into());
+    session.save_session(true).unwrap();
+    let other = Session::new(Some(Uuid::new_v4().to_string())).unwrap();
+    other.save_session(true).unwrap();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("tag")
+        .arg("replay@{1}")
+        .arg(&name)
+        .assert()
+        .success();
+
+    // The name is shown by `list`
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("list")
+        .assert()
+        .stdout(predicates::str::contains(format!(
+            "replay@{{1}} ({}): ",
+            name
+        )));
+
+    // The name is still valid once the indices shifted
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("run")
+        .arg(&name)
+        .arg("--show")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(&marker));
+
+    // So is the id prefix
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("run")
+        .arg(&session.id[..16])
+        .arg("--show")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(&marker));
+
+    // The same name cannot be given twice
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("tag")
+        .arg("replay@{0}")
+        .arg(&name)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("already exists"));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .arg(&name)
+        .assert()
+        .success();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("run")
+        .arg(&name)
+        .arg("--show")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Unknown session"));
+}
+
+#[test]
+#[serial]
+fn test_run_fail_fast() {
+    let marker = Uuid::new_v4().to_string();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin(format!("true\nfalse\necho {}\n", marker))
+        .assert()
+        .success();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("run")
+        .arg("--fail-fast")
+        .assert()
+        .failure()
+        .stdout(
+            predicates::str::is_match(r"(?m)^\s+1\s+0  true$")
+                .unwrap()
+                .and(predicates::str::is_match(r"(?m)^\s+2\s+1  false$").unwrap())
+                .and(predicates::str::contains(
+                    "Stopped at the first failing command",
+                ))
+                .and(predicates::str::contains(&marker).not()),
+        )
+        .stderr(predicates::str::contains(
+            "Command `false` failed with exit status 1",
+        ));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_run_headless() {
+    let marker = Uuid::new_v4().to_string();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin(format!("echo {}\nexit 3\n", marker))
+        .assert()
+        .success();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("run")
+        .arg("--headless")
+        .assert()
+        .code(3)
+        // Once typed in the prompt, once printed by `echo`
+        .stdout(predicates::str::contains(marker.as_str()).count(2))
+        .stderr(predicates::str::contains("Shell exited with status 3"));
+
+    let log_path = std::env::temp_dir().join(format!("{}.log", Uuid::new_v4()));
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("run")
+        .arg("--headless")
+        .arg("--log")
+        .arg(&log_path)
+        .assert()
+        .code(3)
+        .stdout(predicates::str::contains(&marker).not());
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    assert_eq!(log.matches(&marker).count(), 2);
+    std::fs::remove_file(&log_path).unwrap();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_parameterized_session() {
+    let marker = Uuid::new_v4().to_string();
+    let value = Uuid::new_v4().to_string();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin(format!("echo {}-{}\n", marker, value))
+        .assert()
+        .success();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["param", "replay@{0}", &format!("suffix={}", value)])
+        .assert()
+        .success();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["run", "--show"])
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains("Parameters: suffix").and(predicates::str::contains(
+                format!("{}-{{{{suffix}}}}", marker),
+            )),
+        );
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["run", "--headless", "--set", "suffix=substituted"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!("{}-substituted", marker)).count(2));
+
+    // Nothing to prompt for in headless mode
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["run", "--headless"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "No value given for parameter 'suffix'",
+        ));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["run", "--headless", "--set", "unknown=1"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("has no parameter 'unknown'"));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_edit_session() {
+    let marker = Uuid::new_v4().to_string();
+    for cmd in [format!("ech {}", marker), String::from("pwd")] {
+        Command::cargo_bin("replay")
+            .unwrap()
+            .arg("import")
+            .write_stdin(cmd)
+            .assert()
+            .success();
+    }
+    let edited = Session::load_session_by_index(1).unwrap();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["edit", "replay@{1}"])
+        .env_remove("VISUAL")
+        .env(
+            "EDITOR",
+            "sed -i -e s/^ech/echo/ -e 's/^# description:.*/# description: edited session/'",
+        )
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("updated"));
+
+    // Same id, position and compression
+    let session = Session::load_session_by_index(1).unwrap();
+    assert_eq!(session.id, edited.id);
+    assert_eq!(session.description.as_deref(), Some("edited session"));
+    assert_eq!(
+        session.iter_commands().collect::<Vec<_>>(),
+        vec![format!("echo {}\r", marker).as_str(), "exit\r"]
+    );
+    assert!(Session::get_session_path(&session.id, "zst").exists());
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["edit", "replay@{1}", "-d", "edited description"])
+        .assert()
+        .success();
+    let session = Session::load_session_by_index(1).unwrap();
+    assert_eq!(session.description.as_deref(), Some("edited description"));
+
+    // A failing editor leaves the session as is
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["edit", "replay@{1}"])
+        .env("VISUAL", "false")
+        .assert()
+        .failure();
+    let session = Session::load_session_by_index(1).unwrap();
+    assert_eq!(session.commands.len(), 2);
+
+    for _ in 0..2 {
+        Command::cargo_bin("replay")
+            .unwrap()
+            .arg("drop")
+            .assert()
+            .success();
+    }
+}
+
+#[test]
+#[serial]
+fn test_search_sessions() {
+    let marker = format!("needle-{}", Uuid::new_v4());
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin(format!("ls\necho {}\n", marker))
+        .assert()
+        .success();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["search", &marker[..15].to_uppercase(), "-i"])
+        .assert()
+        .success()
+        .stdout(format!("replay@{{0}}\n  2. echo {}\n", marker));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["search", &marker[..15].to_uppercase()])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No session matches"));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_show_session() {
+    let marker = Uuid::new_v4().to_string();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["import", "--no-compression", "-d", "show session test"])
+        .write_stdin(format!("ls\necho {}\n", marker))
+        .assert()
+        .success();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("show")
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains("Description: show session test")
+                .and(predicates::str::contains(format!("  2. echo {}\n", marker))),
+        );
+
+    let output = Command::cargo_bin("replay")
+        .unwrap()
+        .args(["show", "replay@{0}", "--json"])
+        .output()
+        .unwrap();
+    let details: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let session = Session::load_last_session().unwrap();
+    assert_eq!(details["id"], session.id);
+    assert_eq!(details["shell"], "bash");
+    assert_eq!(details["compressed"], false);
+    assert_eq!(details["command_count"], 2);
+    assert_eq!(details["commands"][1], format!("echo {}", marker));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_list_machine_readable() {
+    let marker = Uuid::new_v4().to_string();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("import")
+        .write_stdin(format!("ls\necho {}\npwd\n", marker))
+        .assert()
+        .success();
+    let session = Session::load_last_session().unwrap();
+
+    let output = Command::cargo_bin("replay")
+        .unwrap()
+        .args(["list", "--format", "jsonl", "--limit", "1", "--since", "1h"])
+        .output()
+        .unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["index"], 0);
+    assert_eq!(lines[0]["id"], session.id);
+    assert_eq!(lines[0]["command_count"], 3);
+    assert_eq!(lines[0]["first_commands"][1], format!("echo {}", marker));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args([
+            "list",
+            "--format",
+            "tsv",
+            "--user",
+            &format!("nobody-{}", marker),
+        ])
+        .assert()
+        .success()
+        .stdout("index\tid\ttimestamp\tuser\tcommand_count\tdescription\tfirst_commands\n");
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_run_with_recorded_cwd_and_env() {
+    let marker = Uuid::new_v4().to_string();
+    let mut session = Session::new(None).unwrap();
+    session.add_command(b"echo $REPLAY_TEST_VAR-$(pwd)\r".to_vec());
+    session.add_command(b"exit\r".to_vec());
+    session.cwd = Some(std::env::temp_dir().join(&marker));
+    session
+        .env
+        .insert("REPLAY_TEST_VAR".into(), "recorded".into());
+    session.save_session(true).unwrap();
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["run", "--headless"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("does not exist, use --here"));
+
+    std::fs::create_dir(session.cwd.as_ref().unwrap()).unwrap();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args(["run", "--headless"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "recorded-{}",
+            session.cwd.as_ref().unwrap().display()
+        )));
+    std::fs::remove_dir(session.cwd.as_ref().unwrap()).unwrap();
+
+    let here = std::env::current_dir().unwrap();
+    Command::cargo_bin("replay")
+        .unwrap()
+        .args([
+            "run",
+            "--headless",
+            "--here",
+            "--env",
+            "REPLAY_TEST_VAR=overridden",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "overridden-{}",
+            here.display()
+        )));
+
+    Command::cargo_bin("replay")
+        .unwrap()
+        .arg("drop")
+        .assert()
+        .success();
+}
+
+#[test]
+#[serial]
+fn test_config_set_get_list() {
+    // A temporary home, so the user's real config is never touched
+    let home = std::env::temp_dir().join(format!("replay-home-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&home).unwrap();
+    let path = home.join(".replay/config.toml");
+    let replay = || {
+        let mut cmd = Command::cargo_bin("replay").unwrap();
+        cmd.env("HOME", &home);
+        cmd
+    };
+
+    replay()
+        .args(["config", "get", "run.delay"])
+        .assert()
+        .success()
+        .stdout("10\n");
+
+    replay()
+        .args(["config", "set", "run.delay", "25"])
+        .assert()
+        .success();
+
+    replay()
+        .args(["config", "get", "run.delay"])
+        .assert()
+        .success()
+        .stdout("25\n");
+
+    replay().args(["config", "list"]).assert().success().stdout(
+        predicates::str::contains("run.delay = 25")
+            .and(predicates::str::contains("record.shell = bash")),
+    );
+
+    // Invalid values are rejected and never written
+    replay()
+        .args(["config", "set", "run.delay", "fast"])
+        .assert()
+        .failure();
+    replay()
+        .args(["config", "set", "run.unknown", "1"])
+        .assert()
+        .failure();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "[run]\ndelay = 25\n"
+    );
+
+    std::fs::remove_dir_all(&home).unwrap();
+}